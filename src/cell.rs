@@ -1,20 +1,57 @@
-use crate::{spring::Spring, particle::Particle, dna::CellDna, charge::{ChargeModel, pulse::Pulse, action_potential::ActionPotential}, config::CreatureConfig};
+use crate::{spring::Spring, particle::Particle, dna::CellDna, charge::{self, ChargeModel}, config::CreatureConfig, sensor::SensorKind};
 
 pub struct Cell {
     pub dna: CellDna,
-    pub springs: [Spring; 6],
+    pub springs: Vec<Spring>,
     pub charge_model: Box<dyn ChargeModel + Send>,
     pub pos: (usize, usize),
+    // particle ids, clockwise from the top left
+    pub corners: [usize; 4],
+    // set once any of its springs has snapped
+    pub damaged: bool,
+    pub max_actuation_rate: Option<f64>,
+    pub breaking_strain: Option<f64>,
 }
 
 impl Cell {
-    pub fn update(&mut self, particles: &mut Vec<Particle>, _dt: f64) {
-        let charge = self.charge_model.get_charge();
-        let len_mult = 1.0 + charge * self.dna.reactivity;
+    // moves the rest lengths towards what the control signal, usually the
+    // charge, asks for and snaps overstretched springs, the forces are left
+    // to the integrator. returns the work done by moving the rest lengths
+    // against the spring tension
+    pub fn update(&mut self, particles: &[Particle], signal: f64, dt: f64) -> f64 {
+        let len_mult = 1.0 + signal * self.dna.reactivity;
 
-        for spring in self.springs.iter_mut() {
-            spring.apply(particles);
-            spring.length = spring.start_length * len_mult;
+        let mut work = 0.0;
+        let spring_count = self.springs.len();
+        self.springs.retain_mut(|spring| {
+            let target = spring.start_length * len_mult;
+            let length = match self.max_actuation_rate {
+                Some(rate) => {
+                    let max_change = rate * spring.start_length * dt;
+                    spring.length + (target - spring.length).clamp(-max_change, max_change)
+                },
+                None => target,
+            };
+            if length != spring.length {
+                let tension = spring.k * spring.strain(particles) * spring.length;
+                work += (tension * (length - spring.length)).abs();
+                spring.length = length;
+            }
+
+            !self.breaking_strain.is_some_and(|max_strain| spring.strain(particles).abs() > max_strain)
+        });
+        if self.springs.len() < spring_count {
+            self.damaged = true;
+        }
+        work
+    }
+
+    // none when the charge model has no use for a reading
+    pub fn sensor(&self) -> SensorKind {
+        if self.dna.charge_model.reads_sensors() {
+            self.dna.sensor
+        } else {
+            SensorKind::None
         }
     }
 
@@ -24,11 +61,13 @@ impl Cell {
         }
 
         let diagonal = (options.cell_size * options.cell_size * 2.0).sqrt();
-        let springs = [
+        let springs = vec![
             Spring {
                 a_id: cell_ids[0],
                 b_id: cell_ids[1],
                 k: dna.toughness,
+                damping: dna.damping,
+                max_force: options.max_spring_force,
                 length: options.cell_size,
                 start_length: options.cell_size,
             },
@@ -36,6 +75,8 @@ impl Cell {
                 a_id: cell_ids[1],
                 b_id: cell_ids[2],
                 k: dna.toughness,
+                damping: dna.damping,
+                max_force: options.max_spring_force,
                 length: options.cell_size,
                 start_length: options.cell_size,
             },
@@ -43,6 +84,8 @@ impl Cell {
                 a_id: cell_ids[2],
                 b_id: cell_ids[3],
                 k: dna.toughness,
+                damping: dna.damping,
+                max_force: options.max_spring_force,
                 length: options.cell_size,
                 start_length: options.cell_size,
             },
@@ -50,6 +93,8 @@ impl Cell {
                 a_id: cell_ids[3],
                 b_id: cell_ids[0],
                 k: dna.toughness,
+                damping: dna.damping,
+                max_force: options.max_spring_force,
                 length: options.cell_size,
                 start_length: options.cell_size,
             },
@@ -57,6 +102,8 @@ impl Cell {
                 a_id: cell_ids[0],
                 b_id: cell_ids[2],
                 k: dna.toughness,
+                damping: dna.damping,
+                max_force: options.max_spring_force,
                 length: diagonal,
                 start_length: diagonal,
             },
@@ -64,30 +111,24 @@ impl Cell {
                 a_id: cell_ids[3],
                 b_id: cell_ids[1],
                 k: dna.toughness,
+                damping: dna.damping,
+                max_force: options.max_spring_force,
                 length: diagonal,
                 start_length: diagonal,
             },
         ];
 
-        let charge_model: Box<dyn ChargeModel + Send> = if dna.charge_rate > options.pulse_threshold {
-            Box::new(Pulse::new(
-                dna.charge_rate * 0.5,
-                options.charge_threshold,
-                options.discharge_threshold,
-            ))
-        } else {
-            Box::new(ActionPotential::new(
-                options.discharge_threshold,
-                options.charge_accel,
-                dna.conductivity,
-            ))
-        };
+        let charge_model = charge::build(&dna, &options);
 
         Some(Cell {
             dna,
             springs,
             charge_model,
             pos,
+            corners: cell_ids,
+            damaged: false,
+            max_actuation_rate: options.max_actuation_rate,
+            breaking_strain: options.breaking_strain,
         })
     }
 }
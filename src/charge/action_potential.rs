@@ -72,17 +72,9 @@ impl ChargeModel for ActionPotential {
         }
     }
 
-    fn copy(&self) -> Box<dyn ChargeModel> {
-        Box::new(ActionPotential {
-            charge: self.charge,
-            old_charge: self.old_charge,
-            acceleration: self.acceleration,
-            max_acceleration: self.max_acceleration,
-            threshold: self.threshold,
-            active: self.active,
-            rest_charge: self.rest_charge,
-            potential_threshold: self.potential_threshold,
-        })
+    // a reading over the threshold sets it off just like a discharge
+    fn sense(&mut self, reading: f64) {
+        self.charge(reading);
     }
 }
 
@@ -0,0 +1,192 @@
+// izhikevich's two variable neuron, a membrane potential `v` in millivolts
+// and a slower recovery variable `u`:
+//
+//   v' = 0.04v² + 5v + 140 - u + I
+//   u' = a(bv - u)
+//   if v >= 30: v = c, u = u + d
+//
+// with time in milliseconds. the four parameters pick the firing pattern,
+// the presets are the classic cortical ones
+use super::ChargeModel;
+
+const PEAK_POTENTIAL: f64 = 30.0;
+const REST_POTENTIAL: f64 = -65.0;
+// the model runs at a tenth of real time so its rhythms suit muscles rather
+// than nerves, one simulated second is 100 model milliseconds
+const TIME_SCALE: f64 = 100.0;
+// longer model steps than this are split, the quadratic term blows up with
+// plain euler at a few milliseconds
+const MAX_STEP: f64 = 0.5;
+// drive and discharges are scaled into the model's current units
+const INPUT_SCALE: f64 = 10.0;
+// how quickly a neighbour's discharge wears off, in seconds
+const SYNAPSE_TIME_CONSTANT: f64 = 0.05;
+
+#[derive(Clone, Copy)]
+pub enum IzhikevichPreset {
+    // single spikes, slowing down under constant input
+    RegularSpiking,
+    // a burst of spikes followed by regular single spikes
+    Bursting,
+    // repeated fast bursts
+    Chattering,
+}
+
+impl IzhikevichPreset {
+    // a, b, c, d
+    fn parameters(self) -> (f64, f64, f64, f64) {
+        match self {
+            IzhikevichPreset::RegularSpiking => (0.02, 0.2, -65.0, 8.0),
+            IzhikevichPreset::Bursting => (0.02, 0.2, -55.0, 4.0),
+            IzhikevichPreset::Chattering => (0.02, 0.2, -50.0, 2.0),
+        }
+    }
+}
+
+pub struct Izhikevich {
+    v: f64,
+    u: f64,
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    // constant input
+    drive: f64,
+    // input from neighbouring discharges, decaying
+    synaptic: f64,
+    sensed: f64,
+    // scales each received discharge
+    weight: f64,
+    fired: bool,
+}
+
+impl Izhikevich {
+    pub fn new(preset: IzhikevichPreset, drive: f64, weight: f64) -> Izhikevich {
+        let (a, b, c, d) = preset.parameters();
+        Izhikevich {
+            v: REST_POTENTIAL,
+            u: b * REST_POTENTIAL,
+            a,
+            b,
+            c,
+            d,
+            drive,
+            synaptic: 0.0,
+            sensed: 0.0,
+            weight,
+            fired: false,
+        }
+    }
+}
+
+impl ChargeModel for Izhikevich {
+    // the membrane potential from rest to the spike peak as 0 to 1
+    fn get_charge(&self) -> f64 {
+        ((self.v - REST_POTENTIAL) / (PEAK_POTENTIAL - REST_POTENTIAL)).clamp(0.0, 1.0)
+    }
+
+    fn get_discharge(&self) -> f64 {
+        if self.fired {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn update(&mut self, dt: f64) {
+        let input = (self.drive + self.synaptic + self.sensed) * INPUT_SCALE;
+        self.synaptic *= (-dt / SYNAPSE_TIME_CONSTANT).exp();
+        self.fired = false;
+
+        let time = dt * TIME_SCALE;
+        let steps = (time / MAX_STEP).ceil().max(1.0);
+        let step = time / steps;
+        for _ in 0..steps as usize {
+            let v = self.v;
+            self.v += step * (0.04 * v * v + 5.0 * v + 140.0 - self.u + input);
+            self.u += step * self.a * (self.b * v - self.u);
+
+            if self.v >= PEAK_POTENTIAL {
+                self.v = self.c;
+                self.u += self.d;
+                self.fired = true;
+            }
+        }
+    }
+
+    fn charge(&mut self, amount: f64) {
+        self.synaptic += amount * self.weight;
+    }
+
+    fn sense(&mut self, reading: f64) {
+        self.sensed = reading;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::charge::ChargeModel;
+
+    use super::{Izhikevich, IzhikevichPreset};
+
+    const DT: f64 = 0.0025;
+    // spikes of one burst come closer together than this, in seconds
+    const BURST_INTERVAL: f64 = 0.1;
+
+    // seconds between consecutive spikes over 10 seconds of constant drive
+    fn intervals(preset: IzhikevichPreset, drive: f64) -> Vec<f64> {
+        let mut neuron = Izhikevich::new(preset, drive, 0.0);
+        let mut spikes = Vec::new();
+        for step in 0..4000 {
+            neuron.update(DT);
+            if neuron.get_discharge() > 0.0 {
+                spikes.push(step as f64 * DT);
+            }
+        }
+        spikes.windows(2).map(|pair| pair[1] - pair[0]).collect()
+    }
+
+    #[test]
+    fn stays_at_rest_without_drive() {
+        for preset in [IzhikevichPreset::RegularSpiking, IzhikevichPreset::Bursting, IzhikevichPreset::Chattering] {
+            let mut neuron = Izhikevich::new(preset, 0.0, 0.0);
+            for _ in 0..4000 {
+                neuron.update(DT);
+                assert_eq!(neuron.get_discharge(), 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn regular_spiking_fires_single_spikes_that_slow_down() {
+        let intervals = intervals(IzhikevichPreset::RegularSpiking, 1.0);
+        assert!(intervals.len() > 10);
+        assert!(intervals.iter().all(|interval| *interval > BURST_INTERVAL));
+        // allowing for spike times rounded to the step
+        assert!(intervals.windows(2).all(|pair| pair[1] > pair[0] - DT * 1.5));
+        assert!(intervals[intervals.len() - 1] > intervals[0] * 1.5);
+    }
+
+    #[test]
+    fn bursting_opens_with_a_burst_then_fires_single_spikes() {
+        let intervals = intervals(IzhikevichPreset::Bursting, 1.0);
+        let burst = intervals.iter().take_while(|interval| **interval < BURST_INTERVAL).count();
+        assert!(burst >= 2);
+
+        let tonic = &intervals[burst..];
+        assert!(tonic.len() > 10);
+        assert!(tonic.iter().all(|interval| *interval > BURST_INTERVAL));
+    }
+
+    #[test]
+    fn chattering_fires_repeated_bursts() {
+        let intervals = intervals(IzhikevichPreset::Chattering, 1.0);
+        let bursts: Vec<usize> = intervals.split(|interval| *interval > BURST_INTERVAL)
+            .map(|burst| burst.len() + 1)
+            .collect();
+        assert!(bursts.len() > 10);
+        // the last burst may be cut short by the end of the run
+        assert!(bursts[..bursts.len() - 1].iter().all(|spikes| *spikes >= 3));
+    }
+}
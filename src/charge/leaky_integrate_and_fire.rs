@@ -0,0 +1,135 @@
+// the membrane potential leaks back to rest while its input pushes it up,
+// reaching the threshold fires a spike and resets it. constant input above
+// the threshold gives a regular spike train whose rate rises with the input.
+// potentials are scaled so rest is 0 and the threshold is 1
+use super::ChargeModel;
+
+// seconds for the potential to cover most of the way to its input
+const MEMBRANE_TIME_CONSTANT: f64 = 0.2;
+// after a spike the potential is held at rest this long
+const REFRACTORY_PERIOD: f64 = 0.05;
+// how quickly a neighbour's discharge wears off
+const SYNAPSE_TIME_CONSTANT: f64 = 0.05;
+
+pub struct LeakyIntegrateAndFire {
+    potential: f64,
+    // constant input, above 1 the neuron fires on its own
+    drive: f64,
+    // input from neighbouring discharges, decaying
+    synaptic: f64,
+    sensed: f64,
+    // scales each received discharge
+    weight: f64,
+    refractory: f64,
+    fired: bool,
+}
+
+impl LeakyIntegrateAndFire {
+    pub fn new(drive: f64, weight: f64) -> LeakyIntegrateAndFire {
+        LeakyIntegrateAndFire {
+            potential: 0.0,
+            drive,
+            synaptic: 0.0,
+            sensed: 0.0,
+            weight,
+            refractory: 0.0,
+            fired: false,
+        }
+    }
+}
+
+impl ChargeModel for LeakyIntegrateAndFire {
+    fn get_charge(&self) -> f64 {
+        self.potential
+    }
+
+    fn get_discharge(&self) -> f64 {
+        if self.fired {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn update(&mut self, dt: f64) {
+        let input = self.drive + self.synaptic + self.sensed;
+        self.synaptic *= (-dt / SYNAPSE_TIME_CONSTANT).exp();
+        self.fired = false;
+
+        if self.refractory > 0.0 {
+            self.refractory -= dt;
+            return;
+        }
+
+        // exact for constant input over the step, so large steps can't overshoot
+        let decay = (-dt / MEMBRANE_TIME_CONSTANT).exp();
+        self.potential = input + (self.potential - input) * decay;
+        if self.potential >= 1.0 {
+            self.potential = 0.0;
+            self.refractory = REFRACTORY_PERIOD;
+            self.fired = true;
+        }
+    }
+
+    fn charge(&mut self, amount: f64) {
+        self.synaptic += amount * self.weight;
+    }
+
+    fn sense(&mut self, reading: f64) {
+        self.sensed = reading;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::charge::ChargeModel;
+
+    use super::LeakyIntegrateAndFire;
+
+    // spikes per second over 10 seconds of constant drive
+    fn rate(drive: f64) -> f64 {
+        let mut neuron = LeakyIntegrateAndFire::new(drive, 0.0);
+        let mut spikes = 0;
+        for _ in 0..4000 {
+            neuron.update(0.0025);
+            if neuron.get_discharge() > 0.0 {
+                spikes += 1;
+            }
+        }
+        spikes as f64 / 10.0
+    }
+
+    #[test]
+    fn never_fires_below_threshold() {
+        for drive in [0.0, 0.5, 0.99] {
+            assert_eq!(rate(drive), 0.0);
+        }
+    }
+
+    #[test]
+    fn fires_faster_with_more_drive() {
+        let rates: Vec<f64> = [1.5, 2.0, 3.0, 5.0].into_iter().map(rate).collect();
+        assert!(rates[0] > 0.0);
+        assert!(rates.windows(2).all(|pair| pair[1] > pair[0]), "rates {:?}", rates);
+    }
+
+    #[test]
+    fn a_steady_reading_does_not_depend_on_the_step() {
+        // the reading is sensed every step, as the creature does
+        let spikes = |dt: f64| {
+            let mut neuron = LeakyIntegrateAndFire::new(0.0, 0.0);
+            let mut spikes = 0_usize;
+            for _ in 0..(10.0 / dt).round() as usize {
+                neuron.sense(2.0);
+                neuron.update(dt);
+                if neuron.get_discharge() > 0.0 {
+                    spikes += 1;
+                }
+            }
+            spikes
+        };
+        let (coarse, fine) = (spikes(0.0025), spikes(0.00125));
+        assert!(coarse > 0);
+        assert!(coarse.abs_diff(fine) <= 1, "{} spikes at dt, {} at dt / 2", coarse, fine);
+    }
+}
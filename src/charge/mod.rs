@@ -1,11 +1,81 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{dna::CellDna, config::CreatureConfig};
+
+use self::{pulse::Pulse, action_potential::ActionPotential, oscillator::Oscillator, leaky_integrate_and_fire::LeakyIntegrateAndFire, izhikevich::{Izhikevich, IzhikevichPreset}};
+
 pub mod action_potential;
+pub mod izhikevich;
+pub mod leaky_integrate_and_fire;
+pub mod oscillator;
 pub mod pulse;
 
 pub trait ChargeModel {
     fn get_charge(&self) -> f64;
     fn get_discharge(&self) -> f64;
     fn update(&mut self, dt: f64);
+    // a neighbour's discharge, which arrives once per step it lasts
     fn charge(&mut self, amount: f64);
-    fn copy(&self) -> Box<dyn ChargeModel>;
+    // the cell's sensor reading, a steady input until the next one
+    fn sense(&mut self, reading: f64);
+}
+
+// the charge model gene of a cell
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChargeModelKind {
+    Pulse,
+    ActionPotential,
+    Oscillator,
+    LeakyIntegrateAndFire,
+    RegularSpiking,
+    Bursting,
+    Chattering,
+}
+
+impl ChargeModelKind {
+    // pulses and oscillators keep their own rhythm, a sensor gene on one of
+    // them is skipped
+    pub fn reads_sensors(self) -> bool {
+        !matches!(self, ChargeModelKind::Pulse | ChargeModelKind::Oscillator)
+    }
+}
+
+pub type ChargeModelConstructor = fn(&CellDna, &CreatureConfig) -> Box<dyn ChargeModel + Send>;
+
+// every model a cell can evolve, with how it is built from the cell's genes.
+// a new model needs a kind and an entry here
+pub const CHARGE_MODELS: [(ChargeModelKind, ChargeModelConstructor); 7] = [
+    (ChargeModelKind::Pulse, |dna, options| {
+        Box::new(Pulse::new(dna.charge_rate * 0.5, options.charge_threshold, options.discharge_threshold))
+    }),
+    (ChargeModelKind::ActionPotential, |dna, options| {
+        Box::new(ActionPotential::new(options.discharge_threshold, options.charge_accel, dna.conductivity))
+    }),
+    (ChargeModelKind::Oscillator, |dna, options| {
+        Box::new(Oscillator::new(dna.frequency, dna.amplitude, dna.phase, options.oscillator_coupling))
+    }),
+    (ChargeModelKind::LeakyIntegrateAndFire, |dna, _| {
+        Box::new(LeakyIntegrateAndFire::new(dna.drive, dna.conductivity))
+    }),
+    (ChargeModelKind::RegularSpiking, |dna, _| {
+        Box::new(Izhikevich::new(IzhikevichPreset::RegularSpiking, dna.drive, dna.conductivity))
+    }),
+    (ChargeModelKind::Bursting, |dna, _| {
+        Box::new(Izhikevich::new(IzhikevichPreset::Bursting, dna.drive, dna.conductivity))
+    }),
+    (ChargeModelKind::Chattering, |dna, _| {
+        Box::new(Izhikevich::new(IzhikevichPreset::Chattering, dna.drive, dna.conductivity))
+    }),
+];
+
+pub fn all_kinds() -> Vec<ChargeModelKind> {
+    CHARGE_MODELS.iter().map(|(kind, _)| *kind).collect()
 }
 
+pub fn build(dna: &CellDna, options: &CreatureConfig) -> Box<dyn ChargeModel + Send> {
+    let (_, constructor) = CHARGE_MODELS.iter()
+        .find(|(kind, _)| *kind == dna.charge_model)
+        .expect("every charge model kind is registered");
+    constructor(dna, options)
+}
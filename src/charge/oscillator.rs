@@ -0,0 +1,99 @@
+// a central pattern generator: the charge follows a sine wave, so muscles
+// driven by it contract and relax smoothly at a fixed rhythm. each cycle ends
+// in a discharge, and a discharge received from a neighbour nudges the phase
+// towards `phase_offset`, so coupled oscillators settle into a fixed lag
+// behind whichever neighbour drives them. a discharge lasting several steps
+// nudges it once, when it starts, so the coupling doesn't depend on the step
+use std::f64::consts::TAU;
+
+use super::ChargeModel;
+
+pub struct Oscillator {
+    // cycles per second
+    frequency: f64,
+    amplitude: f64,
+    phase: f64,
+    // the phase this oscillator moves towards when a neighbour discharges
+    phase_offset: f64,
+    // how far a discharge of 1 can shift the phase, in radians
+    coupling: f64,
+    fired: bool,
+    // whether a discharge arrived this step and the step before
+    received: bool,
+    was_receiving: bool,
+}
+
+impl Oscillator {
+    pub fn new(frequency: f64, amplitude: f64, phase_offset: f64, coupling: f64) -> Oscillator {
+        Oscillator {
+            frequency,
+            amplitude,
+            phase: phase_offset.rem_euclid(TAU),
+            phase_offset,
+            coupling,
+            fired: false,
+            received: false,
+            was_receiving: false,
+        }
+    }
+}
+
+impl ChargeModel for Oscillator {
+    fn get_charge(&self) -> f64 {
+        self.amplitude * self.phase.sin()
+    }
+
+    fn get_discharge(&self) -> f64 {
+        if self.fired {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn update(&mut self, dt: f64) {
+        let phase = self.phase + TAU * self.frequency * dt;
+        self.fired = phase >= TAU;
+        self.phase = phase.rem_euclid(TAU);
+        self.was_receiving = self.received;
+        self.received = false;
+    }
+
+    fn charge(&mut self, amount: f64) {
+        self.received = true;
+        if self.was_receiving {
+            return;
+        }
+        let shift = self.coupling * amount * (self.phase_offset - self.phase).sin();
+        self.phase = (self.phase + shift).rem_euclid(TAU);
+    }
+
+    fn sense(&mut self, _reading: f64) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::charge::ChargeModel;
+
+    use super::Oscillator;
+
+    // a neighbour's discharge held for a tenth of a second, then a second
+    // of running free
+    fn phase_after(dt: f64) -> f64 {
+        let mut oscillator = Oscillator::new(1.0, 1.0, 0.0, 0.5);
+        let steps = (1.1 / dt).round() as usize;
+        for step in 0..steps {
+            oscillator.update(dt);
+            if (step as f64) * dt < 0.1 {
+                oscillator.charge(1.0);
+            }
+        }
+        oscillator.phase
+    }
+
+    #[test]
+    fn a_long_discharge_shifts_the_phase_once() {
+        let (coarse, fine) = (phase_after(0.0025), phase_after(0.00125));
+        assert!((coarse - fine).abs() < 0.01, "phase {} at dt, {} at dt / 2", coarse, fine);
+    }
+}
@@ -40,13 +40,6 @@ impl ChargeModel for Pulse {
 
     fn charge(&mut self, _amount: f64) {}
 
-    fn copy(&self) -> Box<dyn ChargeModel> {
-        Box::new(Pulse {
-            charge: self.charge,
-            charge_rate: self.charge_rate,
-            threshold: self.threshold,
-            reset_threshold: self.reset_threshold,
-        })
-    }
+    fn sense(&mut self, _reading: f64) {}
 }
 
@@ -0,0 +1,62 @@
+use std::{fs, path::Path};
+
+use rand_chacha::ChaCha8Rng;
+use serde::{Serialize, Deserialize};
+
+use crate::{config::SimulationConfig, dna::CreatureDna, evolution_controller::GenerationStatistics, hall_of_fame::HallOfFame};
+
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+// everything the controller thread needs to carry on from the start of a
+// generation, so a resumed run makes exactly the same random choices
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub version: u32,
+    pub config: SimulationConfig,
+    pub generation: u32,
+    pub population: Vec<CreatureDna>,
+    pub rng: ChaCha8Rng,
+    pub history: Vec<GenerationStatistics>,
+    pub hall_of_fame: HallOfFame,
+}
+
+impl Checkpoint {
+    pub fn new(config: SimulationConfig, population: Vec<CreatureDna>, rng: ChaCha8Rng) -> Checkpoint {
+        Checkpoint {
+            version: CHECKPOINT_VERSION,
+            hall_of_fame: HallOfFame::new(config.hall_of_fame_size),
+            config,
+            generation: 0,
+            population,
+            rng,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Checkpoint, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("error while reading {}: {}", path.display(), err))?;
+        let checkpoint: Checkpoint = serde_json::from_str(&contents)
+            .map_err(|err| format!("error while parsing {}: {}", path.display(), err))?;
+
+        if checkpoint.version != CHECKPOINT_VERSION {
+            return Err(format!("{}: unsupported checkpoint version {} (expected {})", path.display(), checkpoint.version, CHECKPOINT_VERSION));
+        }
+        checkpoint.config.validate()?;
+
+        Ok(checkpoint)
+    }
+
+    // written to a temporary file first so a crash mid-write never leaves a
+    // truncated checkpoint behind
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string(self)
+            .map_err(|err| format!("error while serialising checkpoint: {}", err))?;
+
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, json)
+            .map_err(|err| format!("error while writing {}: {}", tmp_path.display(), err))?;
+        fs::rename(&tmp_path, path)
+            .map_err(|err| format!("error while writing {}: {}", path.display(), err))
+    }
+}
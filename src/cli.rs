@@ -0,0 +1,82 @@
+use std::path::PathBuf;
+
+pub struct CliOptions {
+    pub headless: bool,
+    pub generations: Option<u32>,
+    pub time_budget: Option<f64>,
+    pub output: PathBuf,
+    pub config_path: Option<PathBuf>,
+    pub overrides: Vec<String>,
+    pub load: Option<PathBuf>,
+    pub checkpoint: Option<PathBuf>,
+    pub resume: Option<PathBuf>,
+}
+
+const USAGE: &str = "usage: evolution-simulator [--headless] [--generations N] [--time-budget SECONDS] [--output PATH]
+                           [--load PATH] [--config PATH] [--set KEY=VALUE]...
+                           [--checkpoint PATH] [--checkpoint-interval N] [--resume PATH]
+                           [--seed N] [--creature-count N] [--threads N] [--sim-time SECONDS]
+                           [--timestep SECONDS] [--sub-steps N]";
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or(format!("missing value for {}", flag))?;
+    value.parse::<T>().map_err(|_| format!("invalid value for {}: {}", flag, value))
+}
+
+// shorthand flags are turned into config overrides so they are validated
+// the same way as `--set`
+fn config_override(key: &str, flag: &str, value: Option<String>) -> Result<String, String> {
+    let value: String = parse_value(flag, value)?;
+    Ok(format!("{}={}", key, value))
+}
+
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<CliOptions, String> {
+    let mut options = CliOptions {
+        headless: false,
+        generations: None,
+        time_budget: None,
+        output: PathBuf::from("best_creature.json"),
+        config_path: None,
+        overrides: Vec::new(),
+        load: None,
+        checkpoint: None,
+        resume: None,
+    };
+
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--headless" => options.headless = true,
+            "--generations" => options.generations = Some(parse_value(&flag, args.next())?),
+            "--time-budget" => options.time_budget = Some(parse_value(&flag, args.next())?),
+            "--output" => options.output = parse_value(&flag, args.next())?,
+            "--config" => options.config_path = Some(parse_value(&flag, args.next())?),
+            "--load" => options.load = Some(parse_value(&flag, args.next())?),
+            "--checkpoint" => options.checkpoint = Some(parse_value(&flag, args.next())?),
+            "--resume" => options.resume = Some(parse_value(&flag, args.next())?),
+            "--set" => options.overrides.push(parse_value(&flag, args.next())?),
+            "--creature-count" => options.overrides.push(config_override("creature_count", &flag, args.next())?),
+            "--threads" => options.overrides.push(config_override("threads", &flag, args.next())?),
+            "--sim-time" => options.overrides.push(config_override("sim_time", &flag, args.next())?),
+            "--timestep" => options.overrides.push(config_override("timestep", &flag, args.next())?),
+            "--sub-steps" => options.overrides.push(config_override("sub_steps", &flag, args.next())?),
+            "--seed" => options.overrides.push(config_override("seed", &flag, args.next())?),
+            "--checkpoint-interval" => options.overrides.push(config_override("checkpoint_interval", &flag, args.next())?),
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            _ => return Err(format!("unknown argument: {}\n{}", flag, USAGE)),
+        }
+    }
+
+    if options.headless && options.generations.is_none() && options.time_budget.is_none() {
+        return Err("headless mode needs --generations or --time-budget".to_string());
+    }
+
+    if options.headless && options.load.is_some() {
+        return Err("--load is only supported with a window".to_string());
+    }
+
+    if options.resume.is_some() && (options.config_path.is_some() || !options.overrides.is_empty()) {
+        return Err("--resume uses the config stored in the checkpoint and can't be combined with config options".to_string());
+    }
+
+    Ok(options)
+}
@@ -0,0 +1,107 @@
+use std::ops::Range;
+
+use crate::{creature::Creature, config::CollisionConfig};
+
+// particles are treated as discs of `radius`. a sweep over particles sorted by
+// x is the broad phase, so only pairs closer than a diameter along x are
+// tested
+pub struct CollisionSolver {
+    self_collision: bool,
+    creature_collision: bool,
+    radius: f64,
+    order: Vec<(f64, usize, usize)>,
+}
+
+// particles of the same cell are held apart by its springs already, and
+// would always overlap for radii close to the cell size
+fn shares_cell(a: usize, b: usize, side_length: usize) -> bool {
+    let (row_a, col_a) = (a / side_length, a % side_length);
+    let (row_b, col_b) = (b / side_length, b % side_length);
+    row_a.abs_diff(row_b) <= 1 && col_a.abs_diff(col_b) <= 1
+}
+
+// pushes both particles out along the line between them, lighter particles
+// move further, and takes away the speed they were approaching each other at
+fn separate(creatures: &mut [Creature], a: (usize, usize), b: (usize, usize), diameter: f64) {
+    let particle_a = &creatures[a.0].particles[a.1];
+    let particle_b = &creatures[b.0].particles[b.1];
+
+    let delta = particle_b.position - particle_a.position;
+    let distance = delta.len();
+    if distance >= diameter || distance == 0.0 {
+        return;
+    }
+
+    let normal = delta / distance;
+    let overlap = diameter - distance;
+    let total_mass = particle_a.mass + particle_b.mass;
+    let correction_a = normal * (overlap * particle_b.mass / total_mass);
+    let correction_b = normal * (overlap * particle_a.mass / total_mass);
+
+    let approach_speed = (particle_a.velocity - particle_b.velocity).dot(normal).max(0.0);
+    let impulse = normal * (approach_speed * particle_a.mass * particle_b.mass / total_mass);
+
+    let particle_a = &mut creatures[a.0].particles[a.1];
+    particle_a.position = particle_a.position - correction_a;
+    particle_a.velocity = particle_a.velocity - impulse / particle_a.mass;
+    let particle_b = &mut creatures[b.0].particles[b.1];
+    particle_b.position = particle_b.position + correction_b;
+    particle_b.velocity = particle_b.velocity + impulse / particle_b.mass;
+}
+
+impl CollisionSolver {
+    pub fn from_config(config: CollisionConfig) -> CollisionSolver {
+        CollisionSolver {
+            self_collision: config.self_collision,
+            creature_collision: config.creature_collision,
+            radius: config.particle_radius,
+            order: Vec::new(),
+        }
+    }
+
+    pub fn resolve(&mut self, creatures: &mut [Creature]) {
+        if !self.self_collision && !self.creature_collision {
+            return;
+        }
+
+        // creatures that can't touch each other are swept one at a time, so
+        // overlapping creatures don't crowd each other's sweeps
+        if self.creature_collision {
+            self.sweep(creatures, 0..creatures.len());
+        } else {
+            for creature_id in 0..creatures.len() {
+                self.sweep(creatures, creature_id..creature_id + 1);
+            }
+        }
+    }
+
+    fn sweep(&mut self, creatures: &mut [Creature], creature_ids: Range<usize>) {
+        self.order.clear();
+        // unstable creatures are frozen, possibly at non-finite positions,
+        // and nothing should bounce off them
+        for creature_id in creature_ids.filter(|creature_id| !creatures[*creature_id].unstable) {
+            for (particle_id, particle) in creatures[creature_id].particles.iter().enumerate() {
+                self.order.push((particle.position.x, creature_id, particle_id));
+            }
+        }
+        self.order.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+        let diameter = self.radius * 2.0;
+        for (i, &(x, creature_a, particle_a)) in self.order.iter().enumerate() {
+            for &(other_x, creature_b, particle_b) in &self.order[i + 1..] {
+                if other_x - x >= diameter {
+                    break;
+                }
+
+                let allowed = if creature_a == creature_b {
+                    self.self_collision && !shares_cell(particle_a, particle_b, creatures[creature_a].size + 1)
+                } else {
+                    self.creature_collision
+                };
+                if allowed {
+                    separate(creatures, (creature_a, particle_a), (creature_b, particle_b), diameter);
+                }
+            }
+        }
+    }
+}
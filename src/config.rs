@@ -1,96 +1,528 @@
-use std::ops::Range;
+use std::{fs, path::Path};
 
-#[derive(Copy, Clone)]
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::{dna::{MutationOperator, crossover::CrossoverOperator}, integrator::Integrator, charge::{self, ChargeModelKind}, sensor::SensorKind};
+
+// heights are measured upwards from `ground_y`. the ground is flat at
+// `ground_y` before the shapes described here and stays at the height of
+// their last point after them
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TerrainConfig {
+    Flat,
+    // piecewise-linear, `heights` are evenly spaced starting at `start`
+    Heightmap { start: f64, spacing: f64, heights: Vec<f64> },
+    // a slope up to `rise`, then a plateau
+    Ramp { start: f64, length: f64, rise: f64 },
+    Steps { start: f64, width: f64, height: f64, count: u32 },
+    Wall { x: f64, thickness: f64, height: f64 },
+    // random heights between 0 and `amplitude`, the same for the same seed
+    Rough { seed: u64, start: f64, length: f64, spacing: f64, amplitude: f64 },
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct CollisionConfig {
+    // between particles of one creature that don't share a cell
+    pub self_collision: bool,
+    // only affects the preview, the simulator never lets creatures collide
+    // so a genome's fitness doesn't depend on the others
+    pub creature_collision: bool,
+    pub particle_radius: f64,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FitnessConfig {
+    // how far right the creature ends up
+    Distance,
+    // distance travelled per unit of muscle work
+    Efficiency,
+    // distance minus `cost` per unit of muscle work
+    Metabolic { cost: f64 },
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct PhysicsConfig {
+    pub integrator: Integrator,
+    // a step leaving any particle faster than this is redone as two half
+    // steps, up to `max_subdivisions` times
+    pub max_speed: f64,
+    pub max_subdivisions: u32,
+    // past this speed, or with non-finite positions, a creature is unstable
+    // and its evaluation ends
+    pub runaway_speed: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct WorldConfig {
     pub ground_y: f64,
-    pub ground_friction: f64,
+    pub terrain: TerrainConfig,
+    // fraction of the normal velocity kept after hitting the ground
+    pub restitution: f64,
+    pub static_friction: f64,
+    pub kinetic_friction: f64,
     pub gravity: f64,
+    pub collision: CollisionConfig,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Serialize, Deserialize)]
 pub struct CreatureConfig {
     pub size: usize,
     pub cell_size: f64,
-    pub pulse_threshold: f64,
     pub charge_threshold: f64,
     pub discharge_threshold: f64,
     pub charge_accel: f64,
     pub active_threshold: f64,
+    // velocities decay as exp(-node_damping * t)
     pub node_damping: f64,
     pub node_mass: f64,
+    // caps the combined spring and damping force of any spring
+    pub max_spring_force: Option<f64>,
+    // how fast muscles can change their rest length, as a fraction of the
+    // original length per second. unset, they jump to their target at once
+    pub max_actuation_rate: Option<f64>,
+    // relative stretch or compression past which a spring snaps
+    pub breaking_strain: Option<f64>,
+    // how strongly oscillator cells pull their phase into line when a
+    // neighbour discharges, 0 leaves them free running
+    pub oscillator_coupling: f64,
+    pub controller: ControllerConfig,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ControllerConfig {
+    // muscles follow their cell's charge, which spreads between neighbours
+    Cellular,
+    // muscles follow a recurrent network with one unit per cell, whose
+    // weights are genes. units settle over `time_constant` seconds and are
+    // fed a clock of `clock_frequency` cycles per second
+    Neural { time_constant: f64, clock_frequency: f64 },
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
 pub struct MutationRange {
     pub min: f64,
     pub max: f64,
+    // overrides `mutation_config.chance` for this gene
+    pub chance: Option<f64>,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct MutationConfig {
+    // every gene mutates on its own with this probability, by steps of
+    // `strength` times its range
     pub chance: f64,
     pub strength: f64,
+    pub operator: MutationOperator,
+    pub self_adaptive: bool,
     pub conductivity: MutationRange,
     pub reactivity: MutationRange,
     pub toughness: MutationRange,
     pub active: MutationRange,
     pub charge_rate: MutationRange,
+    pub damping: MutationRange,
+    pub frequency: MutationRange,
+    pub amplitude: MutationRange,
+    pub phase: MutationRange,
+    pub drive: MutationRange,
+    // the charge models genomes are generated with and can mutate into
+    pub charge_models: Vec<ChargeModelKind>,
+    // overrides `chance` for the charge model gene
+    pub charge_model_chance: Option<f64>,
+    // the sensors genomes are generated with and can mutate into, `none`
+    // among them leaves cells without one
+    pub sensors: Vec<SensorKind>,
+    // overrides `chance` for the sensor gene
+    pub sensor_chance: Option<f64>,
+    pub sensor_gain: MutationRange,
+    // every weight of the neural controller
+    pub weight: MutationRange,
+    pub crossover_rate: f64,
+    pub crossover: CrossoverOperator,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SelectionConfig {
+    Triangular,
+    Tournament { size: usize },
+    Truncation { fraction: f64 },
+    Roulette,
+    Rank { pressure: f64 },
+    StochasticUniversal,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SimulationConfig {
     pub world_config: WorldConfig,
     pub creature_config: CreatureConfig,
+    pub physics: PhysicsConfig,
     pub mutation_config: MutationConfig,
+    pub selection: SelectionConfig,
+    pub fitness: FitnessConfig,
+    // given to genomes whose simulation blew up. when unset they tie with
+    // the worst stable genome of their generation, so they never rank above
+    // one whatever the scale of the fitness function
+    pub unstable_fitness: Option<f64>,
+    pub elite_count: usize,
+    pub hall_of_fame_size: usize,
     pub creature_count: i32, 
     pub timestep: f64,
     pub sub_steps: i32,
     pub sim_time: f64,
     pub threads: i32,
+    pub checkpoint_interval: u32,
+    pub seed: Option<u32>,
 }
 
 impl SimulationConfig {
     pub fn default() -> SimulationConfig {
         let creature_config = CreatureConfig {
             size: 6,
-            node_damping: 4e-2,
+            node_damping: 16.0,
             cell_size: 40.0,
-            pulse_threshold: 1.9,
             charge_threshold: 1.0,
             discharge_threshold: 1.1,
             charge_accel: 300.0,
             active_threshold: 0.2,
             node_mass: 2.0,
+            max_spring_force: None,
+            max_actuation_rate: None,
+            breaking_strain: None,
+            oscillator_coupling: 0.5,
+            controller: ControllerConfig::Cellular,
         };
         let world_config = WorldConfig {
             ground_y: creature_config.cell_size * creature_config.size as f64 + 10.0,
-            ground_friction: 200.0,
+            terrain: TerrainConfig::Flat,
+            restitution: 0.1,
+            static_friction: 1.0,
+            kinetic_friction: 0.8,
             gravity: 800.0,
+            collision: CollisionConfig {
+                self_collision: false,
+                creature_collision: false,
+                particle_radius: creature_config.cell_size * 0.25,
+            },
         };
         let mutation_config = MutationConfig {
-            chance: 0.2,
-            strength: 0.5,
+            chance: 0.01,
+            strength: 0.1,
+            operator: MutationOperator::Gaussian,
+            self_adaptive: false,
             conductivity: mutation_range(0.0, 2.5),
             reactivity: mutation_range(0.0, 0.4),
             toughness: mutation_range(1000.0, 2000.0),
             active: mutation_range(0.0, 1.0),
             charge_rate: mutation_range(0.0, 2.0),
+            damping: mutation_range(0.0, 20.0),
+            frequency: mutation_range(0.2, 3.0),
+            amplitude: mutation_range(0.0, 1.0),
+            phase: mutation_range(0.0, std::f64::consts::TAU),
+            drive: mutation_range(0.0, 3.0),
+            charge_models: charge::all_kinds(),
+            charge_model_chance: None,
+            sensors: vec![SensorKind::None, SensorKind::GroundContact, SensorKind::Tilt, SensorKind::Velocity, SensorKind::Stretch],
+            sensor_chance: None,
+            sensor_gain: mutation_range(0.0, 1.0),
+            weight: mutation_range(-2.0, 2.0),
+            crossover_rate: 0.0,
+            crossover: CrossoverOperator::Block,
         };
 
         SimulationConfig {
             world_config,
             creature_config,
+            physics: PhysicsConfig {
+                integrator: Integrator::SemiImplicitEuler,
+                max_speed: 5000.0,
+                max_subdivisions: 4,
+                runaway_speed: 1e5,
+            },
             mutation_config,
+            selection: SelectionConfig::Triangular,
+            fitness: FitnessConfig::Distance,
+            unstable_fitness: None,
+            elite_count: 1,
+            hall_of_fame_size: 10,
             creature_count: 1000,
             timestep: 0.01,
             sub_steps: 4,
             sim_time: 10.0,
             threads: 6,
+            checkpoint_interval: 10,
+            seed: None,
+        }
+    }
+
+    pub fn load(path: Option<&Path>, overrides: &[String]) -> Result<SimulationConfig, String> {
+        let mut value = serde_json::to_value(SimulationConfig::default())
+            .map_err(|err| format!("error while serialising default config: {}", err))?;
+
+        if let Some(path) = path {
+            let contents = fs::read_to_string(path)
+                .map_err(|err| format!("error while reading {}: {}", path.display(), err))?;
+            let file_value = if is_json(path) {
+                serde_json::from_str::<Value>(&contents)
+                    .map_err(|err| format!("error while parsing {}: {}", path.display(), err))?
+            } else {
+                let toml_value = toml::from_str::<toml::Value>(&contents)
+                    .map_err(|err| format!("error while parsing {}: {}", path.display(), err))?;
+                serde_json::to_value(toml_value)
+                    .map_err(|err| format!("error while parsing {}: {}", path.display(), err))?
+            };
+            merge(&mut value, file_value, "")?;
+        }
+
+        for item in overrides {
+            apply_override(&mut value, item)?;
+        }
+
+        let config: SimulationConfig = serde_json::from_value(value)
+            .map_err(|err| format!("invalid config: {}", err))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = if is_json(path) {
+            serde_json::to_string_pretty(self).map_err(|err| err.to_string())
+        } else {
+            toml::to_string_pretty(self).map_err(|err| err.to_string())
+        }.map_err(|err| format!("error while serialising config: {}", err))?;
+
+        fs::write(path, contents)
+            .map_err(|err| format!("error while writing {}: {}", path.display(), err))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors: Vec<String> = Vec::new();
+
+        if self.threads < 1 {
+            errors.push(format!("threads must be at least 1, got {}", self.threads));
+        }
+        if self.sub_steps < 1 {
+            errors.push(format!("sub_steps must be at least 1, got {}", self.sub_steps));
+        }
+        if self.creature_count < 1 {
+            errors.push(format!("creature_count must be at least 1, got {}", self.creature_count));
+        }
+        if self.elite_count > self.creature_count.max(0) as usize {
+            errors.push(format!("elite_count ({}) must not be greater than creature_count ({})", self.elite_count, self.creature_count));
+        }
+        if self.timestep <= 0.0 {
+            errors.push(format!("timestep must be positive, got {}", self.timestep));
+        }
+        if self.sim_time <= 0.0 {
+            errors.push(format!("sim_time must be positive, got {}", self.sim_time));
+        }
+        if self.creature_config.size < 1 {
+            errors.push("creature_config.size must be at least 1".to_string());
+        }
+        if self.creature_config.cell_size <= 0.0 {
+            errors.push(format!("creature_config.cell_size must be positive, got {}", self.creature_config.cell_size));
+        }
+        if self.creature_config.node_mass <= 0.0 {
+            errors.push(format!("creature_config.node_mass must be positive, got {}", self.creature_config.node_mass));
+        }
+        if self.creature_config.node_damping < 0.0 {
+            errors.push(format!("creature_config.node_damping must not be negative, got {}", self.creature_config.node_damping));
+        }
+        if self.creature_config.oscillator_coupling < 0.0 {
+            errors.push(format!("creature_config.oscillator_coupling must not be negative, got {}", self.creature_config.oscillator_coupling));
+        }
+        if let ControllerConfig::Neural { time_constant, clock_frequency } = self.creature_config.controller {
+            if time_constant <= 0.0 {
+                errors.push(format!("creature_config.controller: time_constant must be positive, got {}", time_constant));
+            }
+            if clock_frequency < 0.0 {
+                errors.push(format!("creature_config.controller: clock_frequency must not be negative, got {}", clock_frequency));
+            }
+        }
+        if self.mutation_config.frequency.min < 0.0 {
+            errors.push(format!("mutation_config.frequency must not go below 0, got {}", self.mutation_config.frequency.min));
+        }
+        if self.physics.max_speed <= 0.0 {
+            errors.push(format!("physics.max_speed must be positive, got {}", self.physics.max_speed));
+        }
+        if self.physics.runaway_speed < self.physics.max_speed {
+            errors.push(format!("physics.runaway_speed ({}) must not be less than max_speed ({})", self.physics.runaway_speed, self.physics.max_speed));
+        }
+        if let Integrator::Xpbd { iterations: 0 } = self.physics.integrator {
+            errors.push("physics.integrator: xpbd needs at least 1 iteration".to_string());
+        }
+        let limits = [
+            ("max_spring_force", self.creature_config.max_spring_force),
+            ("max_actuation_rate", self.creature_config.max_actuation_rate),
+            ("breaking_strain", self.creature_config.breaking_strain),
+        ];
+        for (name, limit) in limits {
+            if let Some(limit) = limit.filter(|limit| *limit <= 0.0) {
+                errors.push(format!("creature_config.{} must be positive when set, got {}", name, limit));
+            }
+        }
+        let world = &self.world_config;
+        if !(0.0..=1.0).contains(&world.restitution) {
+            errors.push(format!("world_config.restitution must be between 0 and 1, got {}", world.restitution));
+        }
+        if world.static_friction < 0.0 || world.kinetic_friction < 0.0 {
+            errors.push("world_config: friction coefficients must not be negative".to_string());
+        }
+        if world.kinetic_friction > world.static_friction {
+            errors.push(format!("world_config.kinetic_friction ({}) must not be greater than static_friction ({})", world.kinetic_friction, world.static_friction));
+        }
+        if world.collision.particle_radius <= 0.0 {
+            errors.push(format!("world_config.collision.particle_radius must be positive, got {}", world.collision.particle_radius));
+        }
+        match &world.terrain {
+            TerrainConfig::Heightmap { spacing, heights, .. } => {
+                if *spacing <= 0.0 {
+                    errors.push(format!("world_config.terrain: heightmap spacing must be positive, got {}", spacing));
+                }
+                if heights.is_empty() {
+                    errors.push("world_config.terrain: heightmap needs at least one height".to_string());
+                }
+            },
+            TerrainConfig::Ramp { length, .. } if *length <= 0.0 => {
+                errors.push(format!("world_config.terrain: ramp length must be positive, got {}", length));
+            },
+            TerrainConfig::Steps { width, .. } if *width <= 0.0 => {
+                errors.push(format!("world_config.terrain: step width must be positive, got {}", width));
+            },
+            TerrainConfig::Wall { thickness, .. } if *thickness < 0.0 => {
+                errors.push(format!("world_config.terrain: wall thickness must not be negative, got {}", thickness));
+            },
+            TerrainConfig::Rough { length, spacing, .. } if *length <= 0.0 || *spacing <= 0.0 => {
+                errors.push("world_config.terrain: rough ground length and spacing must be positive".to_string());
+            },
+            _ => {},
+        }
+        if !(0.0..=1.0).contains(&self.mutation_config.chance) {
+            errors.push(format!("mutation_config.chance must be between 0 and 1, got {}", self.mutation_config.chance));
+        }
+
+        if self.mutation_config.strength < 0.0 {
+            errors.push(format!("mutation_config.strength must not be negative, got {}", self.mutation_config.strength));
+        }
+        if !(0.0..=1.0).contains(&self.mutation_config.crossover_rate) {
+            errors.push(format!("mutation_config.crossover_rate must be between 0 and 1, got {}", self.mutation_config.crossover_rate));
+        }
+
+        match self.selection {
+            SelectionConfig::Tournament { size } if size < 1 => {
+                errors.push("selection: tournament size must be at least 1".to_string());
+            },
+            SelectionConfig::Truncation { fraction } if fraction <= 0.0 || fraction > 1.0 => {
+                errors.push(format!("selection: truncation fraction must be in (0, 1], got {}", fraction));
+            },
+            SelectionConfig::Rank { pressure } if !(1.0..=2.0).contains(&pressure) => {
+                errors.push(format!("selection: rank pressure must be between 1 and 2, got {}", pressure));
+            },
+            _ => {},
+        }
+
+        if let Some(fitness) = self.unstable_fitness {
+            if !fitness.is_finite() {
+                errors.push(format!("unstable_fitness must be finite, got {}", fitness));
+            }
+        }
+        if let FitnessConfig::Metabolic { cost } = self.fitness {
+            if cost < 0.0 {
+                errors.push(format!("fitness: metabolic cost must not be negative, got {}", cost));
+            }
+        }
+
+        let mutation = &self.mutation_config;
+        if mutation.charge_models.is_empty() {
+            errors.push("mutation_config.charge_models needs at least one model".to_string());
+        }
+        if mutation.sensors.is_empty() {
+            errors.push("mutation_config.sensors needs at least one sensor, or none".to_string());
+        }
+        let ranges = [
+            ("conductivity", mutation.conductivity),
+            ("reactivity", mutation.reactivity),
+            ("toughness", mutation.toughness),
+            ("active", mutation.active),
+            ("charge_rate", mutation.charge_rate),
+            ("damping", mutation.damping),
+            ("frequency", mutation.frequency),
+            ("amplitude", mutation.amplitude),
+            ("phase", mutation.phase),
+            ("drive", mutation.drive),
+            ("sensor_gain", mutation.sensor_gain),
+            ("weight", mutation.weight),
+        ];
+        for (name, range) in ranges {
+            if range.min > range.max {
+                errors.push(format!("mutation_config.{}: min ({}) must not be greater than max ({})", name, range.min, range.max));
+            }
+        }
+        let chances = ranges.iter().map(|(name, range)| (*name, range.chance))
+            .chain([("charge_model", mutation.charge_model_chance), ("sensor", mutation.sensor_chance)]);
+        for (name, chance) in chances {
+            if let Some(chance) = chance.filter(|chance| !(0.0..=1.0).contains(chance)) {
+                errors.push(format!("mutation_config.{}: chance must be between 0 and 1 when set, got {}", name, chance));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("invalid config:\n  {}", errors.join("\n  ")))
         }
     }
 }
 
 fn mutation_range(min: f64, max: f64) -> MutationRange {
-    MutationRange { min, max }
+    MutationRange { min, max, chance: None }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+}
+
+// tagged enums like `selection` are replaced wholesale when the overlay picks
+// a different `kind`, since their fields differ between variants
+fn merge(base: &mut Value, overlay: Value, prefix: &str) -> Result<(), String> {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) if overlay.contains_key("kind") && overlay.get("kind") != base.get("kind") => {
+            *base = overlay;
+            Ok(())
+        },
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                let name = format!("{}{}", prefix, key);
+                let entry = base.get_mut(&key)
+                    .ok_or(format!("unknown config key: {}", name))?;
+                merge(entry, value, &format!("{}.", name))?;
+            }
+            Ok(())
+        },
+        (base, overlay) => {
+            *base = overlay;
+            Ok(())
+        },
+    }
+}
+
+// overrides look like `creature_config.size=8`, values are parsed as JSON
+// and fall back to a plain string
+fn apply_override(config: &mut Value, item: &str) -> Result<(), String> {
+    let (key, raw_value) = item.split_once('=')
+        .ok_or(format!("invalid override '{}', expected key=value", item))?;
+
+    let mut target = config;
+    for part in key.trim().split('.') {
+        target = target.get_mut(part)
+            .ok_or(format!("unknown config key: {}", key))?;
+    }
+
+    let raw_value = raw_value.trim();
+    *target = serde_json::from_str(raw_value).unwrap_or(Value::String(raw_value.to_string()));
+    Ok(())
 }
 
@@ -1,49 +1,174 @@
-use crate::{cell::Cell, particle::Particle, vec2::Vec2, dna::CreatureDna, config::CreatureConfig};
+use crate::{cell::Cell, particle::Particle, spring::Spring, vec2::Vec2, dna::CreatureDna, config::{CreatureConfig, PhysicsConfig}, integrator, energy::EnergyStats, sensor::{self, Senses, SensorKind}, neural::NeuralController, config::ControllerConfig};
 
 pub struct Creature {
     pub particles: Vec<Particle>,
     pub cells: Vec<Option<Cell>>,
     pub size: usize,
+    // centre of mass when the creature was created
+    pub origin: Vec2,
+    pub energy: EnergyStats,
+    // drives the muscles in place of the cells' charges when set
+    pub controller: Option<NeuralController>,
+    // set when a step blew up even at the smallest sub-step, the creature is
+    // frozen from then on and gets the penalty fitness
+    pub unstable: bool,
+    buffers: Buffers,
+}
+
+// working space for a step, kept between steps so stepping doesn't allocate
+#[derive(Default)]
+struct Buffers {
+    positions: Vec<Vec2>,
+    velocities: Vec<Vec2>,
+    discharges: Vec<((usize, usize), f64)>,
+    scratch: integrator::Scratch,
 }
 
 impl Creature {
-    pub fn update(&mut self, dt: f64) {
-        let mut discharges: Vec<((usize, usize), f64)> = vec![];
-        for cell in self.cells.iter_mut() {
-            if let Some(cell) = cell {
-                cell.update(&mut self.particles, dt);
-                cell.charge_model.update(dt);
-
-                let discharge = cell.charge_model.get_discharge();
-                if discharge > 0.0 {
-                    discharges.push((cell.pos, discharge));
-                }
+    pub fn update(&mut self, dt: f64, physics: PhysicsConfig) {
+        for cell in self.cells.iter_mut().flatten() {
+            let signal = Creature::signal(self.controller.as_ref(), cell);
+            self.energy.actuation_work += cell.update(&self.particles, signal, dt);
+        }
+
+        if self.needs_senses() {
+            self.sense(dt);
+        }
+        self.update_charges(dt);
+
+        self.step_physics(dt, physics, 0);
+        for particle in self.particles.iter_mut() {
+            particle.acceleration = Vec2 { x: 0.0, y: 0.0 };
+        }
+    }
+
+    // what the cell's muscles follow, its charge or the network's output
+    pub fn signal(controller: Option<&NeuralController>, cell: &Cell) -> f64 {
+        match controller {
+            Some(network) => network.output(cell.pos),
+            None => cell.charge_model.get_charge(),
+        }
+    }
+
+    // the network always reads the sensors, charges only when a cell has one
+    pub fn needs_senses(&self) -> bool {
+        self.controller.is_some() || self.cells.iter().flatten().any(|cell| cell.sensor() != SensorKind::None)
+    }
+
+    // advances the network, or hands every sensor cell its reading
+    fn sense(&mut self, dt: f64) {
+        let senses = Senses { particles: &self.particles, size: self.size };
+        if let Some(network) = self.controller.as_mut() {
+            network.update(&self.cells, &senses, dt);
+            return;
+        }
+
+        for cell in self.cells.iter_mut().flatten().filter(|cell| cell.sensor() != SensorKind::None) {
+            let reading = sensor::read(cell.sensor(), cell.dna.sensor_gain, &senses, cell.corners, &cell.springs);
+            cell.charge_model.sense(reading);
+        }
+    }
+
+    // advances every cell's charge and passes discharges on to the
+    // neighbouring cells. the muscles read the charge before this runs.
+    // charges are unused under a network
+    pub fn update_charges(&mut self, dt: f64) {
+        if self.controller.is_some() {
+            return;
+        }
+
+        let mut discharges = std::mem::take(&mut self.buffers.discharges);
+        discharges.clear();
+        for cell in self.cells.iter_mut().flatten() {
+            cell.charge_model.update(dt);
+
+            let discharge = cell.charge_model.get_discharge();
+            if discharge > 0.0 {
+                discharges.push((cell.pos, discharge));
             }
         }
 
+        let size = self.size as isize;
         for (pos, discharge) in discharges.iter() {
-            let (row, col) = *pos;
-            let neighbors = [
-                (row, col + 1),
-                (row, col - 1),
-                (row + 1, col),
-                (row - 1, col),
-            ];
-            for (row, col) in neighbors {
-                if col > self.size - 1 {
+            let (row, col) = (pos.0 as isize, pos.1 as isize);
+            for (row_offset, col_offset) in [(0, 1), (0, -1), (1, 0), (-1, 0)] {
+                let (row, col) = (row + row_offset, col + col_offset);
+                if row < 0 || col < 0 || row >= size || col >= size {
                     continue;
                 }
-                if let Some(cell) = self.cells.get_mut(col + row * self.size) {
-                    if let Some(cell) = cell {
-                        cell.charge_model.charge(*discharge);
-                    }
+                if let Some(cell) = &mut self.cells[(col + row * size) as usize] {
+                    cell.charge_model.charge(*discharge);
                 }
             }
         }
+        self.buffers.discharges = discharges;
+    }
 
-        for particle in self.particles.iter_mut() {
-            particle.integrate(dt)
+    // a step that leaves any particle faster than `max_speed` is taken to
+    // have blown up, and is thrown away and redone as two half steps. once
+    // the sub-steps can't get any smaller the step is kept, unless it is
+    // beyond saving, in which case the creature is marked unstable
+    fn step_physics(&mut self, dt: f64, physics: PhysicsConfig, depth: u32) {
+        // taken out while the integrator borrows the creature, and put back
+        // before any half steps so they reuse it
+        let mut buffers = std::mem::take(&mut self.buffers);
+        let Buffers { positions, velocities, scratch, .. } = &mut buffers;
+        positions.clear();
+        positions.extend(self.particles.iter().map(|particle| particle.position));
+        velocities.clear();
+        velocities.extend(self.particles.iter().map(|particle| particle.velocity));
+        integrator::step(physics.integrator, self, positions, velocities, scratch, dt);
+
+        // written so that NaN counts as unstable too
+        let max_speed_sqr = physics.max_speed * physics.max_speed;
+        let stable = velocities.iter().all(|velocity| velocity.sqr_len() <= max_speed_sqr);
+        let subdivide = !stable && depth < physics.max_subdivisions;
+
+        let runaway_speed_sqr = physics.runaway_speed * physics.runaway_speed;
+        let runaway = !subdivide && positions.iter().zip(velocities.iter()).any(|(position, velocity)| {
+            let finite = position.x.is_finite() && position.y.is_finite() && velocity.x.is_finite() && velocity.y.is_finite();
+            !finite || velocity.sqr_len() > runaway_speed_sqr
+        });
+        if !subdivide && !runaway {
+            for ((particle, position), velocity) in self.particles.iter_mut().zip(positions.iter()).zip(velocities.iter()) {
+                particle.position = *position;
+                particle.velocity = *velocity;
+            }
         }
+        self.buffers = buffers;
+
+        if subdivide {
+            self.step_physics(dt * 0.5, physics, depth + 1);
+            self.step_physics(dt * 0.5, physics, depth + 1);
+        } else if runaway {
+            self.unstable = true;
+        }
+    }
+
+    pub fn springs(&self) -> impl Iterator<Item = &Spring> {
+        self.cells.iter().flatten().flat_map(|cell| cell.springs.iter())
+    }
+
+    // external accelerations plus the spring forces for the given state
+    pub fn accelerations(&self, positions: &[Vec2], velocities: &[Vec2], accelerations: &mut [Vec2]) {
+        for (acceleration, particle) in accelerations.iter_mut().zip(self.particles.iter()) {
+            *acceleration = particle.acceleration;
+        }
+
+        for spring in self.springs() {
+            let (a, b) = (spring.a_id, spring.b_id);
+            let force = spring.force(positions[a], positions[b], velocities[a], velocities[b]);
+            accelerations[a] = accelerations[a] + force / self.particles[a].mass;
+            accelerations[b] = accelerations[b] - force / self.particles[b].mass;
+        }
+    }
+
+    pub fn centre_of_mass(&self) -> Vec2 {
+        let total_mass: f64 = self.particles.iter().map(|particle| particle.mass).sum();
+        let weighted = self.particles.iter().fold(Vec2 { x: 0.0, y: 0.0 }, |sum, particle| {
+            sum + particle.position * particle.mass
+        });
+        weighted / total_mass
     }
 
     pub fn get_cell_id(row: usize, col: usize, side_length: usize) -> usize {
@@ -58,10 +183,11 @@ impl Creature {
 
                 particles.push(Particle {
                     position: Vec2 { x: x + 10.0, y },
-                    old_position: Vec2 { x: x + 10.0, y },
+                    velocity: Vec2 { x: 0.0, y: 0.0 },
                     acceleration: Vec2 { x: 0.0, y: 0.0 },
                     mass: options.node_mass,
                     damping: options.node_damping,
+                    grounded: false,
                 })
             }
         }
@@ -80,17 +206,69 @@ impl Creature {
                 cells.push(Cell::new(
                     ids,
                     options,
-                    cell_dna.clone(),
+                    *cell_dna,
                     (row, col),
                 ));
             }
         }
 
-        Some(Creature {
+        let mut creature = Creature {
             particles,
             cells,
             size: options.size,
-        })
+            origin: Vec2 { x: 0.0, y: 0.0 },
+            energy: EnergyStats::default(),
+            controller: match options.controller {
+                ControllerConfig::Cellular => None,
+                ControllerConfig::Neural { time_constant, clock_frequency } => {
+                    Some(NeuralController::new(options.size, time_constant, clock_frequency))
+                },
+            },
+            unstable: false,
+            buffers: Buffers::default(),
+        };
+        creature.origin = creature.centre_of_mass();
+        Some(creature)
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::{config::SimulationConfig, charge::ChargeModelKind, sensor::SensorKind, dna};
+
+    use super::Creature;
+
+    #[test]
+    fn discharges_reach_only_the_neighbours_inside_the_grid() {
+        let config = SimulationConfig::default();
+        let size = config.creature_config.size;
+        let mut dna = dna::generate_dna(size * size, &config.mutation_config, &mut ChaCha8Rng::seed_from_u64(0));
+        for cell in dna.iter_mut() {
+            cell.active = 1.0;
+            cell.charge_model = ChargeModelKind::LeakyIntegrateAndFire;
+            cell.conductivity = 1.0;
+            cell.drive = 0.0;
+            cell.sensor = SensorKind::None;
+        }
+        // the top left corner fires on its own after about 0.045 seconds
+        dna[0].drive = 5.0;
+
+        let mut creature = Creature::new(config.creature_config, dna).unwrap();
+        for _ in 0..25 {
+            creature.update_charges(0.0025);
+        }
+
+        for (index, cell) in creature.cells.iter().enumerate() {
+            let potential = cell.as_ref().unwrap().charge_model.get_charge();
+            match (index / size, index % size) {
+                (0, 1) | (1, 0) => assert!(potential > 0.0, "neighbour {} was not charged", index),
+                (0, 0) => {},
+                _ => assert_eq!(potential, 0.0, "cell {} was charged", index),
+            }
+        }
+    }
+}
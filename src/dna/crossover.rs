@@ -0,0 +1,62 @@
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+
+use super::CreatureDna;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrossoverOperator {
+    Uniform,
+    OnePoint,
+    TwoPoint,
+    Block,
+}
+
+// picks every cell from either parent with equal chance
+fn uniform<R: Rng>(a: &CreatureDna, b: &CreatureDna, rng: &mut R) -> CreatureDna {
+    a.iter().zip(b.iter()).map(|(cell_a, cell_b)| {
+        if rng.gen::<bool>() { *cell_a } else { *cell_b }
+    }).collect()
+}
+
+// takes cells from `b` in the range `start..end` and from `a` everywhere else
+fn splice(a: &CreatureDna, b: &CreatureDna, start: usize, end: usize) -> CreatureDna {
+    a.iter().zip(b.iter()).enumerate().map(|(i, (cell_a, cell_b))| {
+        if i >= start && i < end { *cell_b } else { *cell_a }
+    }).collect()
+}
+
+fn one_point<R: Rng>(a: &CreatureDna, b: &CreatureDna, rng: &mut R) -> CreatureDna {
+    let point = rng.gen_range(0..=a.len());
+    splice(a, b, point, a.len())
+}
+
+fn two_point<R: Rng>(a: &CreatureDna, b: &CreatureDna, rng: &mut R) -> CreatureDna {
+    let first = rng.gen_range(0..=a.len());
+    let second = rng.gen_range(0..=a.len());
+    splice(a, b, first.min(second), first.max(second))
+}
+
+// swaps in a rectangular region of `b`'s grid so neighbouring cells, and so
+// body parts, are inherited together
+fn block<R: Rng>(a: &CreatureDna, b: &CreatureDna, size: usize, rng: &mut R) -> CreatureDna {
+    let rows = (rng.gen_range(0..size), rng.gen_range(0..size));
+    let cols = (rng.gen_range(0..size), rng.gen_range(0..size));
+    let (row_start, row_end) = (rows.0.min(rows.1), rows.0.max(rows.1));
+    let (col_start, col_end) = (cols.0.min(cols.1), cols.0.max(cols.1));
+
+    a.iter().zip(b.iter()).enumerate().map(|(i, (cell_a, cell_b))| {
+        let (row, col) = (i / size, i % size);
+        let inside = row >= row_start && row <= row_end && col >= col_start && col <= col_end;
+        if inside { *cell_b } else { *cell_a }
+    }).collect()
+}
+
+pub fn crossover<R: Rng>(a: &CreatureDna, b: &CreatureDna, size: usize, operator: CrossoverOperator, rng: &mut R) -> CreatureDna {
+    match operator {
+        CrossoverOperator::Uniform => uniform(a, b, rng),
+        CrossoverOperator::OnePoint => one_point(a, b, rng),
+        CrossoverOperator::TwoPoint => two_point(a, b, rng),
+        CrossoverOperator::Block => block(a, b, size, rng),
+    }
+}
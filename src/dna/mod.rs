@@ -1,67 +1,188 @@
-use rand::random;
+use rand::Rng;
+use serde::{Serialize, Deserialize};
 
-use crate::config::{MutationConfig, MutationRange};
+use crate::{config::{MutationConfig, MutationRange}, charge::ChargeModelKind, sensor::SensorKind, neural::WEIGHT_COUNT};
 
-const NUM_FIELDS: f64 = 5.0;
+pub mod storage;
+pub mod crossover;
 
-#[derive(Clone, Copy)]
+// the real valued genes `mutate_cell` visits before the weights
+const FIELD_COUNT: usize = 11;
+pub const STEP_SIZE_COUNT: usize = FIELD_COUNT + WEIGHT_COUNT;
+
+const STEP_SIZE_LEARNING_RATE: f64 = 0.2;
+const MIN_STEP_SIZE: f64 = 0.01;
+const MAX_STEP_SIZE: f64 = 10.0;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MutationOperator {
+    // adds noise with a standard deviation of `strength` times the range width
+    Gaussian,
+    // picks a new value anywhere in the range
+    UniformReset,
+    // adds a uniform step of up to `strength` times the range width
+    Creep,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct CellDna {
     pub conductivity: f64,
     pub reactivity: f64,
     pub toughness: f64,
     pub active: f64,
     pub charge_rate: f64,
+    pub damping: f64,
+    pub charge_model: ChargeModelKind,
+    // genes only some charge models read
+    pub frequency: f64,
+    pub amplitude: f64,
+    pub phase: f64,
+    pub drive: f64,
+    pub sensor: SensorKind,
+    pub sensor_gain: f64,
+    // the cell's unit in the neural controller
+    pub weights: [f64; WEIGHT_COUNT],
+    // one per real valued gene, in the order `mutate_cell` visits them
+    pub step_sizes: [f64; STEP_SIZE_COUNT],
 }
 
 pub type CreatureDna = Vec<CellDna>;
 
-fn generate_field(range: MutationRange) -> f64 {
-    range.min + rand::random::<f64>() * (range.max - range.min)
+fn default_step_sizes() -> [f64; STEP_SIZE_COUNT] {
+    [1.0; STEP_SIZE_COUNT]
+}
+
+fn generate_field<R: Rng>(range: MutationRange, rng: &mut R) -> f64 {
+    range.min + rng.gen::<f64>() * (range.max - range.min)
 }
 
-pub fn generate_dna(length: usize, config: MutationConfig) -> CreatureDna {
+// categorical genes are drawn uniformly from the allowed choices, the config
+// is validated to have at least one
+fn generate_choice<T: Copy, R: Rng>(choices: &[T], rng: &mut R) -> T {
+    choices[rng.gen_range(0..choices.len())]
+}
+
+pub fn generate_dna<R: Rng>(length: usize, config: &MutationConfig, rng: &mut R) -> CreatureDna {
     let mut dna: CreatureDna = Vec::new();
 
     for _ in 0..length {
         dna.push(CellDna {
-            conductivity: generate_field(config.conductivity),
-            reactivity: generate_field(config.reactivity),
-            toughness: generate_field(config.toughness),
-            active: generate_field(config.active),
-            charge_rate: generate_field(config.charge_rate),
+            conductivity: generate_field(config.conductivity, rng),
+            reactivity: generate_field(config.reactivity, rng),
+            toughness: generate_field(config.toughness, rng),
+            active: generate_field(config.active, rng),
+            charge_rate: generate_field(config.charge_rate, rng),
+            damping: generate_field(config.damping, rng),
+            charge_model: generate_choice(&config.charge_models, rng),
+            frequency: generate_field(config.frequency, rng),
+            amplitude: generate_field(config.amplitude, rng),
+            phase: generate_field(config.phase, rng),
+            drive: generate_field(config.drive, rng),
+            sensor: generate_choice(&config.sensors, rng),
+            sensor_gain: generate_field(config.sensor_gain, rng),
+            weights: std::array::from_fn(|_| generate_field(config.weight, rng)),
+            step_sizes: default_step_sizes(),
         })
     }
 
     dna
 }
 
-fn apply_mutation(value: f64, multiplier: f64, range: MutationRange) -> f64 {
-    let result = value * multiplier;
+fn gaussian<R: Rng>(rng: &mut R) -> f64 {
+    // box-muller, 1 - gen() keeps the log argument above zero
+    let u1 = 1.0 - rng.gen::<f64>();
+    let u2 = rng.gen::<f64>();
+    (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+}
+
+fn mutate_gene<R: Rng>(value: f64, range: MutationRange, step: f64, operator: MutationOperator, rng: &mut R) -> f64 {
+    let width = range.max - range.min;
+    let result = match operator {
+        MutationOperator::Gaussian => value + gaussian(rng) * step * width,
+        MutationOperator::UniformReset => generate_field(range, rng),
+        MutationOperator::Creep => value + (rng.gen::<f64>() * 2.0 - 1.0) * step * width,
+    };
     result.clamp(range.min, range.max)
 }
 
-fn mutate_cell(cell: &mut CellDna, field: i32, config: MutationConfig) {
-    let multiplier = (random::<f64>() * 2.0 - 1.0) * config.strength;
-    match field {
-        1 => cell.reactivity = apply_mutation(cell.reactivity, multiplier, config.reactivity),
-        2 => cell.toughness = apply_mutation(cell.toughness, multiplier, config.toughness),
-        3 => cell.active = apply_mutation(cell.active, multiplier, config.active),
-        4 => cell.charge_rate = apply_mutation(cell.charge_rate, multiplier, config.charge_rate),
-        _ => cell.conductivity = apply_mutation(cell.conductivity, multiplier, config.conductivity),
+fn mutate_cell<R: Rng>(cell: &mut CellDna, config: &MutationConfig, rng: &mut R) {
+    let genes: [(&mut f64, MutationRange); FIELD_COUNT] = [
+        (&mut cell.conductivity, config.conductivity),
+        (&mut cell.reactivity, config.reactivity),
+        (&mut cell.toughness, config.toughness),
+        (&mut cell.active, config.active),
+        (&mut cell.charge_rate, config.charge_rate),
+        (&mut cell.damping, config.damping),
+        (&mut cell.frequency, config.frequency),
+        (&mut cell.amplitude, config.amplitude),
+        (&mut cell.phase, config.phase),
+        (&mut cell.drive, config.drive),
+        (&mut cell.sensor_gain, config.sensor_gain),
+    ];
+    let weights = cell.weights.iter_mut().map(|weight| (weight, config.weight));
+    for ((gene, range), step_size) in genes.into_iter().chain(weights).zip(cell.step_sizes.iter_mut()) {
+        // each gene's step size is perturbed log-normally before it is used,
+        // so step sizes that produce fitter children are inherited with them
+        if config.self_adaptive {
+            let factor = (STEP_SIZE_LEARNING_RATE * gaussian(rng)).exp();
+            *step_size = (*step_size * factor).clamp(MIN_STEP_SIZE, MAX_STEP_SIZE);
+        }
+        if rng.gen::<f64>() < range.chance.unwrap_or(config.chance) {
+            let step = config.strength * if config.self_adaptive { *step_size } else { 1.0 };
+            *gene = mutate_gene(*gene, range, step, config.operator, rng);
+        }
+    }
+
+    // a switch of model keeps the other genes, which the new model may read
+    // differently
+    if rng.gen::<f64>() < config.charge_model_chance.unwrap_or(config.chance) {
+        cell.charge_model = generate_choice(&config.charge_models, rng);
+    }
+    if rng.gen::<f64>() < config.sensor_chance.unwrap_or(config.chance) {
+        cell.sensor = generate_choice(&config.sensors, rng);
     }
 }
 
-pub fn mutate_dna(dna: &CreatureDna, config: MutationConfig) -> CreatureDna {
+// every gene mutates independently with its own probability, `chance` unless
+// the config overrides it for that gene
+pub fn mutate_dna<R: Rng>(dna: &CreatureDna, config: &MutationConfig, rng: &mut R) -> CreatureDna {
     let mut new_dna = dna.clone();
 
-    let chance_roll = random::<f64>();
-    if chance_roll > config.chance {
-        let id = random::<f64>() * new_dna.len() as f64;
-        let field = random::<f64>() * NUM_FIELDS;
-
-        mutate_cell(&mut new_dna[id as usize], field as i32, config);
+    for cell in new_dna.iter_mut() {
+        mutate_cell(cell, config, rng);
     }
 
     new_dna
 }
 
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::config::SimulationConfig;
+
+    use super::{generate_dna, mutate_dna};
+
+    #[test]
+    fn a_gene_chance_overrides_the_global_one() {
+        let mut config = SimulationConfig::default().mutation_config;
+        config.chance = 0.0;
+        config.conductivity.chance = Some(1.0);
+        config.sensor_chance = Some(1.0);
+        config.self_adaptive = true;
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let parent = generate_dna(36, &config, &mut rng);
+        let child = mutate_dna(&parent, &config, &mut rng);
+
+        assert!(parent.iter().zip(child.iter()).any(|(parent, child)| parent.conductivity != child.conductivity));
+        assert!(parent.iter().zip(child.iter()).any(|(parent, child)| parent.sensor != child.sensor));
+        for (parent, child) in parent.iter().zip(child.iter()) {
+            assert!(parent.reactivity == child.reactivity && parent.weights == child.weights && parent.charge_model == child.charge_model);
+            // every step size adapts on its own
+            assert!(child.step_sizes.iter().any(|step_size| *step_size != child.step_sizes[0]));
+        }
+    }
+}
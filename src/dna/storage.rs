@@ -0,0 +1,92 @@
+use std::{fs, path::Path};
+
+use serde::{Serialize, Deserialize};
+
+use crate::{config::CreatureConfig, evolution_controller::CreatureResult};
+
+use super::CreatureDna;
+
+// bump this whenever CellDna or the layout below changes
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct PopulationEntry {
+    dna: CreatureDna,
+    fitness: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum DnaFileContents {
+    Genome { dna: CreatureDna },
+    Population { creatures: Vec<PopulationEntry> },
+}
+
+#[derive(Serialize, Deserialize)]
+struct DnaFile {
+    version: u32,
+    size: usize,
+    #[serde(flatten)]
+    contents: DnaFileContents,
+}
+
+fn write_file(path: &Path, file: &DnaFile) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(file)
+        .map_err(|err| format!("error while serialising dna: {}", err))?;
+    fs::write(path, json)
+        .map_err(|err| format!("error while writing {}: {}", path.display(), err))
+}
+
+fn check_dna(dna: &CreatureDna, size: usize, path: &Path) -> Result<(), String> {
+    if dna.len() != size * size {
+        return Err(format!("{}: genome has {} cells but a size {} grid needs {}", path.display(), dna.len(), size, size * size));
+    }
+    Ok(())
+}
+
+pub fn save_genome(path: &Path, dna: &CreatureDna, size: usize) -> Result<(), String> {
+    write_file(path, &DnaFile {
+        version: FORMAT_VERSION,
+        size,
+        contents: DnaFileContents::Genome { dna: dna.clone() },
+    })
+}
+
+pub fn save_population(path: &Path, results: &[CreatureResult], size: usize) -> Result<(), String> {
+    let creatures = results.iter().map(|(dna, fitness)| {
+        PopulationEntry { dna: dna.clone(), fitness: *fitness }
+    }).collect();
+
+    write_file(path, &DnaFile {
+        version: FORMAT_VERSION,
+        size,
+        contents: DnaFileContents::Population { creatures },
+    })
+}
+
+// loads either a single genome or every genome of a population, refusing
+// files that were evolved for a different grid size
+pub fn load_genomes(path: &Path, config: CreatureConfig) -> Result<Vec<CreatureDna>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| format!("error while reading {}: {}", path.display(), err))?;
+    let file: DnaFile = serde_json::from_str(&contents)
+        .map_err(|err| format!("error while parsing {}: {}", path.display(), err))?;
+
+    if file.version != FORMAT_VERSION {
+        return Err(format!("{}: unsupported format version {} (expected {})", path.display(), file.version, FORMAT_VERSION));
+    }
+    if file.size != config.size {
+        return Err(format!("{}: evolved for size {} but creature_config.size is {}", path.display(), file.size, config.size));
+    }
+
+    let genomes: Vec<CreatureDna> = match file.contents {
+        DnaFileContents::Genome { dna } => vec![dna],
+        DnaFileContents::Population { creatures } => creatures.into_iter().map(|entry| entry.dna).collect(),
+    };
+
+    for dna in genomes.iter() {
+        check_dna(dna, file.size, path)?;
+    }
+
+    Ok(genomes)
+}
@@ -0,0 +1,27 @@
+use crate::vec2::Vec2;
+
+// energy bookkeeping for one creature over its simulation. the potential is
+// gravitational, measured upwards from the flat ground level
+#[derive(Clone, Copy, Default)]
+pub struct EnergyStats {
+    // work the muscles did changing their rest lengths. positive and negative
+    // work both count, a muscle burns energy either way
+    pub actuation_work: f64,
+    pub kinetic: f64,
+    pub potential: f64,
+    pub peak_kinetic: f64,
+}
+
+impl EnergyStats {
+    // `particles` yields the mass, position and velocity of each particle
+    pub fn record(&mut self, particles: impl Iterator<Item = (f64, Vec2, Vec2)> + Clone, gravity: f64, ground_y: f64) {
+        self.kinetic = particles.clone().map(|(mass, _, velocity)| {
+            0.5 * mass * velocity.sqr_len()
+        }).sum();
+        self.potential = particles.map(|(mass, position, _)| {
+            mass * gravity * (ground_y - position.y)
+        }).sum();
+
+        self.peak_kinetic = self.peak_kinetic.max(self.kinetic);
+    }
+}
@@ -1,189 +1,483 @@
-use std::{sync::{mpsc::{self, Sender, Receiver, SendError}, Arc}, thread};
+use std::{sync::{mpsc::{self, Sender, Receiver, SendError, RecvError, TryRecvError}, Arc, Mutex, atomic::{AtomicBool, Ordering}}, thread::{self, JoinHandle}, path::{Path, PathBuf}};
 
 use chrono::UTC;
-use rand::{Rng, thread_rng};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Serialize, Deserialize};
 
-use crate::{config::{SimulationConfig, MutationConfig}, simulator::{Simulator, SimulatorMessage}, dna::{CreatureDna, generate_dna, mutate_dna}, fitness::FitnessFunction};
+use crate::{config::{SimulationConfig, MutationConfig}, simulator::{SimulatorPool, SimulationOutcome}, dna::{CreatureDna, generate_dna, mutate_dna, crossover::crossover}, fitness, checkpoint::Checkpoint, selection::{self, SelectionStrategy}, hall_of_fame::HallOfFameEntry};
 
 const LOG_OWNER: &str = "[evolution_controller]";
 
 pub type CreatureResult = (CreatureDna, f64);
 
-pub enum ControllerMessage {
+pub enum ControllerCommand {
     Start,
+    Pause,
+    Step,
+    Cancel,
     Stop,
-    Results(Vec<CreatureResult>),
+    Shutdown,
+}
+
+pub enum ControllerEvent {
+    GenerationStarted { generation: u32 },
+    // simulation steps completed so far in the current generation
+    Progress { steps: usize, total_steps: usize },
+    GenerationFinished { statistics: GenerationStatistics, results: Vec<CreatureResult> },
+    Paused,
+    Stopped { results: Vec<CreatureResult> },
+    Error(String),
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct GenerationStatistics {
+    pub generation: u32,
+    pub best: f64,
+    pub median: f64,
+    pub mean: f64,
+    // genomes that blew up and were given the penalty fitness
+    pub unstable: usize,
 }
 
 pub struct EvolutionController {
-    message_sender: Sender<ControllerMessage>,
-    message_receiver: Receiver<ControllerMessage>,
+    command_sender: Sender<ControllerCommand>,
+    event_receiver: Receiver<ControllerEvent>,
+    cancel: Arc<AtomicBool>,
     running: bool,
+    config: SimulationConfig,
+    hall_of_fame: Arc<Mutex<Vec<HallOfFameEntry>>>,
+    thread: Option<JoinHandle<()>>,
 }
 
-fn simulate_generation(dna: &Vec<CreatureDna>, simulators: &Vec<Simulator>) -> Vec<CreatureResult> {
-    let creature_count = dna.len();
-    let batch_size = creature_count / simulators.len();
-    for (i, simulator) in simulators.iter().enumerate() {
-        let slice = &dna[i * batch_size..(i + 1) * batch_size];
-        simulator.start(slice);
-    }
-
-    let mut results: Vec<CreatureResult> = Vec::new();
-    for simulator in simulators.iter() {
-        let message = simulator.message_receiver.recv();
-        match message {
-            Ok(result) => {
-                if let SimulatorMessage::Results(mut result) = result {
-                    results.append(&mut result);
-                }
-            },
-            Err(msg) => eprintln!("{}: error while reading channel: {:?}", LOG_OWNER, msg)
+// each parent produces one child, which is crossed with a random mate from
+// the other parents `crossover_rate` of the time before being mutated
+fn reproduce<R: Rng>(config: &MutationConfig, size: usize, parents: &[CreatureDna], rng: &mut R) -> Vec<CreatureDna> {
+    parents.iter().map(|dna| {
+        if rng.gen::<f64>() < config.crossover_rate {
+            let mate = &parents[rng.gen_range(0..parents.len())];
+            let child = crossover(dna, mate, size, config.crossover, rng);
+            mutate_dna(&child, config, rng)
+        } else {
+            mutate_dna(dna, config, rng)
         }
-    }
-
-    return results;
+    }).collect()
 }
 
-fn select_fittest(results: &Vec<(CreatureDna, f64)>) -> Vec<CreatureDna> {
-    let mut sorted = results.clone();
-
-    let mut rng = thread_rng();
-    for _ in 0..results.len() / 2 {
-        let id = (rng.gen::<f64>() - 0.5) * sorted.len() as f64;
-        sorted.remove(id.abs() as usize);
+// expects results sorted by ascending fitness
+fn generation_statistics(generation: u32, results: &[CreatureResult], unstable: usize) -> GenerationStatistics {
+    let total: f64 = results.iter().map(|(_, fitness)| fitness).sum();
+    GenerationStatistics {
+        generation,
+        best: results.last().map_or(0.0, |(_, fitness)| *fitness),
+        median: results.get(results.len() / 2).map_or(0.0, |(_, fitness)| *fitness),
+        mean: total / results.len().max(1) as f64,
+        unstable,
     }
+}
 
-    sorted.into_iter().map(|(dna, _)| { dna }).collect()
+// elites are copied over unchanged, the rest of the population is bred from
+// the selected parents. expects results sorted by ascending fitness
+fn breed(state: &mut Checkpoint, results: &[CreatureResult], selection: &dyn SelectionStrategy) {
+    let config = &state.config;
+    let elite_count = config.elite_count.min(results.len());
+    let elites = results.iter().rev().take(elite_count).map(|(dna, _)| dna.clone());
+    let parents = selection.select(results, config.creature_count as usize - elite_count, &mut state.rng);
+    let children = reproduce(&config.mutation_config, config.creature_config.size, &parents, &mut state.rng);
+
+    state.population = elites.chain(children).collect();
+    state.generation += 1;
 }
 
-fn reproduce(config: MutationConfig, initial: &Vec<CreatureDna>) -> Vec<CreatureDna> {
-    initial.iter().flat_map(|dna| {
-        [mutate_dna(dna, config), mutate_dna(dna, config)]
-    }).collect()
+fn save_checkpoint(state: &Checkpoint, path: &Option<PathBuf>) -> Result<(), String> {
+    if let Some(path) = path {
+        state.save(path).map_err(|msg| format!("error while writing checkpoint: {}", msg))?;
+        println!("{}: checkpoint written at generation {}", LOG_OWNER, state.generation);
+    }
+    Ok(())
 }
 
 impl EvolutionController {
-    fn generate_dna(config: SimulationConfig) -> Vec<CreatureDna> {
+    fn generate_dna<R: Rng>(config: &SimulationConfig, rng: &mut R) -> Vec<CreatureDna> {
         let mut dna: Vec<CreatureDna> = Vec::with_capacity(config.creature_count as usize);
         let creature_size = config.creature_config.size;
         for _ in 0..config.creature_count {
-            dna.push(generate_dna(creature_size * creature_size, config.mutation_config));
+            dna.push(generate_dna(creature_size * creature_size, &config.mutation_config, rng));
         }
         dna
     }
 
-    pub fn new(config: SimulationConfig, fitness_func: FitnessFunction) -> EvolutionController {
-        let fitness = Arc::new(fitness_func);
-        let simulators = (0..config.threads).into_iter().map(|_| {
-            Simulator::from_config(config, fitness.clone())
-        }).collect();
+    // runs without a seed get a random one, which is stored in the config so
+    // the effective config can reproduce the run
+    pub fn new(config: SimulationConfig, checkpoint_path: Option<PathBuf>) -> EvolutionController {
+        let seed = config.seed.unwrap_or_else(rand::random);
+        let config = SimulationConfig { seed: Some(seed), ..config };
+        println!("{}: using seed {}", LOG_OWNER, seed);
 
-        let mut running = false;
-        let mut dna = Self::generate_dna(config);
-        let mut results: Vec<CreatureResult> = Vec::new();
+        let mut rng = ChaCha8Rng::seed_from_u64(seed as u64);
+        let dna = Self::generate_dna(&config, &mut rng);
 
-        let (main_sender, thread_receiver) = mpsc::channel::<ControllerMessage>();
-        let (thread_sender, main_receiver) = mpsc::channel::<ControllerMessage>();
+        Self::from_checkpoint(Checkpoint::new(config, dna, rng), checkpoint_path)
+    }
 
-        thread::spawn(move || loop {
-            let result = thread_receiver.try_recv();
-            if let Ok(message) = result {
-                match message {
-                    ControllerMessage::Start => {
-                        running = true;
-                        println!("{}: controller running", LOG_OWNER);
-                    },
-                    ControllerMessage::Stop => {
-                        running = false;
-                        println!("{}: controller stopping", LOG_OWNER);
+    // continues a run from a checkpoint file, new checkpoints are written
+    // back to `checkpoint_path` if given, otherwise to the file resumed from
+    pub fn resume(path: &Path, checkpoint_path: Option<PathBuf>) -> Result<EvolutionController, String> {
+        let checkpoint = Checkpoint::load(path)?;
+        println!("{}: resuming from generation {}", LOG_OWNER, checkpoint.generation);
 
-                        let send_result = thread_sender.send(ControllerMessage::Results(results.clone()));
-                        if let Err(msg) = send_result {
-                            eprintln!("{}: error while sending results: {:?}", LOG_OWNER, msg);
-                        }
-                    },
-                    _ => {},
+        let checkpoint_path = checkpoint_path.unwrap_or(path.to_path_buf());
+        Ok(Self::from_checkpoint(checkpoint, Some(checkpoint_path)))
+    }
+
+    fn from_checkpoint(mut state: Checkpoint, checkpoint_path: Option<PathBuf>) -> EvolutionController {
+        let config = state.config.clone();
+
+        let hall_of_fame = Arc::new(Mutex::new(state.hall_of_fame.entries().to_vec()));
+        let thread_hall_of_fame = hall_of_fame.clone();
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let thread_cancel = cancel.clone();
+
+        let selection = selection::from_config(config.selection);
+        let fitness = Arc::new(fitness::from_config(config.fitness));
+        let simulators = SimulatorPool::from_config(config.clone(), fitness, cancel.clone());
+
+        let (command_sender, command_receiver) = mpsc::channel::<ControllerCommand>();
+        let (event_sender, event_receiver) = mpsc::channel::<ControllerEvent>();
+
+        let thread = thread::spawn(move || {
+            let send = |event: ControllerEvent| {
+                if let Err(msg) = event_sender.send(event) {
+                    eprintln!("{}: error while sending event: {:?}", LOG_OWNER, msg);
+                }
+            };
+
+            let mut running = false;
+            let mut single_step = false;
+            let mut results: Vec<CreatureResult> = Vec::new();
+
+            loop {
+                // block while idle so an idle controller costs nothing, while
+                // running only check for commands between generations
+                let command = if running {
+                    match command_receiver.try_recv() {
+                        Ok(command) => Some(command),
+                        Err(TryRecvError::Empty) => None,
+                        Err(TryRecvError::Disconnected) => break,
+                    }
+                } else {
+                    match command_receiver.recv() {
+                        Ok(command) => Some(command),
+                        Err(_) => break,
+                    }
                 };
-            }
 
-            if running {
+                if let Some(command) = command {
+                    match command {
+                        ControllerCommand::Start => {
+                            thread_cancel.store(false, Ordering::SeqCst);
+                            running = true;
+                            single_step = false;
+                            println!("{}: controller running", LOG_OWNER);
+                        },
+                        ControllerCommand::Step => {
+                            thread_cancel.store(false, Ordering::SeqCst);
+                            running = true;
+                            single_step = true;
+                        },
+                        ControllerCommand::Pause | ControllerCommand::Cancel => {
+                            if running {
+                                running = false;
+                                send(ControllerEvent::Paused);
+                            }
+                        },
+                        ControllerCommand::Stop => {
+                            running = false;
+                            println!("{}: controller stopping", LOG_OWNER);
+                            if let Err(msg) = save_checkpoint(&state, &checkpoint_path) {
+                                send(ControllerEvent::Error(msg));
+                            }
+                            send(ControllerEvent::Stopped { results: results.clone() });
+                        },
+                        ControllerCommand::Shutdown => break,
+                    }
+                    continue;
+                }
+
+                // the cancel flag is only cleared by start and step, so a cancel
+                // sent just before a generation begins still takes effect
+                let generation = state.generation + 1;
+                send(ControllerEvent::GenerationStarted { generation });
                 let start = UTC::now();
 
-                results = simulate_generation(&dna, &simulators);
+                let mut progress = (0, 0);
+                let outcome = simulators.simulate(&state.population, |steps, total_steps| {
+                    progress = (steps, total_steps);
+                    send(ControllerEvent::Progress { steps, total_steps });
+                });
+                let unstable;
+                (results, unstable) = match outcome {
+                    SimulationOutcome::Finished { results, unstable } => (results, unstable),
+                    SimulationOutcome::Cancelled => {
+                        println!("{}: generation {} cancelled after {} of {} steps", LOG_OWNER, generation, progress.0, progress.1);
+                        continue;
+                    },
+                    SimulationOutcome::Failed(msg) => {
+                        running = false;
+                        send(ControllerEvent::Error(msg));
+                        continue;
+                    },
+                };
                 results.sort_by(|(_, a), (_, b)| {
                     a.total_cmp(b)
                 });
-                let fittest = select_fittest(&results);
-                dna = reproduce(config.mutation_config, &fittest);
+                breed(&mut state, &results, selection.as_ref());
 
-                let best_fitness = match results.last() {
-                    Some((_, fitness)) => fitness.clone(),
-                    None => 0.0,
-                };
+                let statistics = generation_statistics(state.generation, &results, unstable);
+                state.history.push(statistics);
+
+                state.hall_of_fame.record(state.generation, &results);
+                if let Ok(mut shared) = thread_hall_of_fame.lock() {
+                    *shared = state.hall_of_fame.entries().to_vec();
+                }
 
                 let end = UTC::now();
                 let time = end - start;
-                println!("{}: generation completed in {}ms, best {}", LOG_OWNER, time.num_milliseconds(), best_fitness);
+                println!("{}: generation {} completed in {}ms, best {}, {} unstable", LOG_OWNER, statistics.generation, time.num_milliseconds(), statistics.best, statistics.unstable);
 
-                let send_result = thread_sender.send(ControllerMessage::Results(results.clone()));
-                if let Err(msg) = send_result {
-                    eprintln!("{}: error while sending results: {:?}", LOG_OWNER, msg);
+                // an error always leaves the controller idle, so a run never
+                // carries on without its checkpoints
+                if config.checkpoint_interval > 0 && state.generation.is_multiple_of(config.checkpoint_interval) {
+                    if let Err(msg) = save_checkpoint(&state, &checkpoint_path) {
+                        running = false;
+                        send(ControllerEvent::Error(msg));
+                    }
+                }
+
+                send(ControllerEvent::GenerationFinished { statistics, results: results.clone() });
+
+                if single_step {
+                    running = false;
+                    single_step = false;
+                    send(ControllerEvent::Paused);
                 }
             }
+            // dropping the pool here cancels and joins the simulator threads
         });
 
         EvolutionController {
-            message_sender: main_sender,
-            message_receiver: main_receiver,
+            command_sender,
+            event_receiver,
+            cancel,
             running: false,
+            config,
+            hall_of_fame,
+            thread: Some(thread),
         }
     }
 
-    pub fn start(&mut self) -> Result<(), SendError<ControllerMessage>> {
-        let result = self.message_sender.send(ControllerMessage::Start);
-        if let Ok(_) = result {
-            self.running = true;
+    pub fn hall_of_fame(&self) -> Vec<HallOfFameEntry> {
+        match self.hall_of_fame.lock() {
+            Ok(entries) => entries.clone(),
+            Err(_) => Vec::new(),
         }
-        result
     }
 
-    pub fn try_get_results(&self) -> Vec<CreatureResult> {
-        self.message_receiver.try_recv().into_iter()
-            .filter_map(|message| {
-                match message {
-                    ControllerMessage::Results(results) => Some(results),
-                    _ => None,
-                }
-            })
-            .flatten()
-            .collect()
-    }
-    
-    pub fn stop(&mut self) -> Result<Vec<CreatureResult>, SendError<ControllerMessage>> {
-        let send_result = self.message_sender.send(ControllerMessage::Stop);
-        if let Err(err) = send_result {
-            return Err(err);
-        }
+    pub fn config(&self) -> SimulationConfig {
+        self.config.clone()
+    }
 
-        println!("{}: collecting results (will wait for final generation)...", LOG_OWNER);
-        let results: Vec<CreatureResult> = self.message_receiver.recv().into_iter()
-            .filter_map(|message| {
-                match message {
-                    ControllerMessage::Results(results) => Some(results),
-                    _ => None,
-                }
-            })
-            .flatten()
-            .collect();
+    fn send(&self, command: ControllerCommand) -> Result<(), SendError<ControllerCommand>> {
+        self.command_sender.send(command)
+    }
+
+    // runs generations continuously, also resumes after a pause
+    pub fn start(&mut self) -> Result<(), SendError<ControllerCommand>> {
+        self.send(ControllerCommand::Start)
+    }
+
+    // lets the current generation finish, then waits for further commands
+    pub fn pause(&mut self) -> Result<(), SendError<ControllerCommand>> {
+        self.send(ControllerCommand::Pause)
+    }
+
+    // runs exactly one generation, then pauses
+    pub fn step(&mut self) -> Result<(), SendError<ControllerCommand>> {
+        self.send(ControllerCommand::Step)
+    }
+
+    // abandons the generation in progress and pauses, the population is left
+    // as it was before that generation
+    pub fn cancel(&mut self) -> Result<(), SendError<ControllerCommand>> {
+        self.cancel.store(true, Ordering::SeqCst);
+        self.send(ControllerCommand::Cancel)
+    }
+
+    // like cancel, but also writes a checkpoint and reports the results of the
+    // last completed generation with a `Stopped` event
+    pub fn stop(&mut self) -> Result<(), SendError<ControllerCommand>> {
+        self.cancel.store(true, Ordering::SeqCst);
+        self.send(ControllerCommand::Stop)
+    }
+
+    // abandons the generation in progress and waits for the controller and
+    // simulator threads to exit. also done when the controller is dropped
+    pub fn shutdown(&mut self) -> Result<(), SendError<ControllerCommand>> {
+        let thread = match self.thread.take() {
+            Some(thread) => thread,
+            None => return Ok(()),
+        };
 
-        println!("{}: results gathered!", LOG_OWNER);
         self.running = false;
-        Ok(results)
+        self.cancel.store(true, Ordering::SeqCst);
+        self.send(ControllerCommand::Shutdown)?;
+        if thread.join().is_err() {
+            eprintln!("{}: controller thread panicked", LOG_OWNER);
+        }
+        Ok(())
+    }
+
+    // the controller thread is the only one that knows when a run ends, so
+    // `running` follows its events
+    fn track(&mut self, event: &ControllerEvent) {
+        match event {
+            ControllerEvent::GenerationStarted { .. } => self.running = true,
+            ControllerEvent::Paused | ControllerEvent::Stopped { .. } | ControllerEvent::Error(_) => self.running = false,
+            _ => {},
+        }
+    }
+
+    pub fn poll_events(&mut self) -> Vec<ControllerEvent> {
+        let events: Vec<ControllerEvent> = self.event_receiver.try_iter().collect();
+        for event in events.iter() {
+            self.track(event);
+        }
+        events
+    }
+
+    pub fn next_event(&mut self) -> Result<ControllerEvent, RecvError> {
+        let event = self.event_receiver.recv()?;
+        self.track(&event);
+        Ok(event)
     }
 
+    // as of the last event received
     pub fn is_running(&self) -> bool {
         self.running
     }
 }
 
+impl Drop for EvolutionController {
+    fn drop(&mut self) {
+        if let Err(msg) = self.shutdown() {
+            eprintln!("{}: error while shutting down controller: {:?}", LOG_OWNER, msg);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{config::SimulationConfig, checkpoint::Checkpoint};
+
+    use super::{EvolutionController, ControllerEvent, GenerationStatistics, CreatureResult};
+
+    fn config(creature_count: i32, threads: i32, steps: usize) -> SimulationConfig {
+        let mut config = SimulationConfig::default();
+        config.creature_count = creature_count;
+        config.threads = threads;
+        config.sim_time = steps as f64 * config.timestep / config.sub_steps as f64;
+        config.seed = Some(7);
+        config
+    }
+
+    // every generation's statistics and results, in order
+    fn run(config: SimulationConfig, generations: usize) -> Vec<(GenerationStatistics, Vec<CreatureResult>)> {
+        finish(&mut EvolutionController::new(config, None), generations)
+    }
+
+    fn finish(controller: &mut EvolutionController, generations: usize) -> Vec<(GenerationStatistics, Vec<CreatureResult>)> {
+        controller.start().unwrap();
+
+        let mut finished = Vec::new();
+        while finished.len() < generations {
+            match controller.next_event().unwrap() {
+                ControllerEvent::GenerationFinished { statistics, results } => finished.push((statistics, results)),
+                ControllerEvent::Error(msg) => panic!("{}", msg),
+                _ => {},
+            }
+        }
+        finished
+    }
+
+    #[test]
+    fn population_size_is_kept_across_generations() {
+        // one step per genome, split unevenly across threads and chunks
+        let config = config(1000, 6, 1);
+        for (_, results) in run(config, 3) {
+            assert_eq!(results.len(), 1000);
+        }
+    }
+
+    fn statistics_bits(statistics: &GenerationStatistics) -> Vec<u64> {
+        vec![statistics.best.to_bits(), statistics.median.to_bits(), statistics.mean.to_bits()]
+    }
+
+    fn fitnesses(run: Vec<(GenerationStatistics, Vec<CreatureResult>)>) -> Vec<Vec<u64>> {
+        run.iter().map(|(statistics, results)| {
+            let mut bits = statistics_bits(statistics);
+            bits.extend(results.iter().map(|(_, fitness)| fitness.to_bits()));
+            bits
+        }).collect()
+    }
+
+    #[test]
+    fn same_seed_and_threads_give_the_same_history() {
+        let config = config(40, 3, 200);
+        let first = fitnesses(run(config.clone(), 4));
+        let second = fitnesses(run(config.clone(), 4));
+        assert_eq!(first, second);
+
+        let other_seed = SimulationConfig { seed: Some(8), ..config };
+        assert_ne!(first, fitnesses(run(other_seed, 4)));
+    }
+
+    #[test]
+    fn a_resumed_run_matches_a_straight_one() {
+        let config = SimulationConfig { checkpoint_interval: 2, ..config(40, 3, 200) };
+        let straight = fitnesses(run(config.clone(), 4));
+
+        // the checkpoint is written before the second generation is reported,
+        // and dropping the controller abandons the third
+        let path = std::env::temp_dir().join(format!("resume-{}.json", std::process::id()));
+        let first_half = fitnesses(finish(&mut EvolutionController::new(config, Some(path.clone())), 2));
+        let mut resumed = EvolutionController::resume(&path, None).unwrap();
+        let second_half = fitnesses(finish(&mut resumed, 2));
+        drop(resumed);
+
+        let checkpoint = Checkpoint::load(&path);
+        fs::remove_file(&path).unwrap();
+        let history: Vec<Vec<u64>> = checkpoint.unwrap().history.iter().map(statistics_bits).collect();
+        assert_eq!([first_half, second_half].concat(), straight);
+        assert_eq!(history, straight.iter().map(|bits| bits[..3].to_vec()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn an_error_leaves_the_controller_idle() {
+        let config = SimulationConfig { checkpoint_interval: 1, ..config(40, 3, 1) };
+        let path = std::env::temp_dir().join(format!("missing-{}", std::process::id())).join("checkpoint.json");
+        let mut controller = EvolutionController::new(config, Some(path));
+        controller.start().unwrap();
+
+        loop {
+            match controller.next_event().unwrap() {
+                ControllerEvent::GenerationStarted { .. } => assert!(controller.is_running()),
+                ControllerEvent::Error(_) => break,
+                _ => {},
+            }
+        }
+        assert!(!controller.is_running());
+    }
+}
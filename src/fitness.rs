@@ -1,8 +1,19 @@
-use crate::creature::Creature;
+use crate::{creature::Creature, config::FitnessConfig};
 
-pub type FitnessFunction = Box<dyn Fn(&Vec<Creature>) -> Vec<f64> + Send + Sync>;
+pub type FitnessFunction = Box<dyn Fn(&[Creature]) -> Vec<f64> + Send + Sync>;
 
-pub fn fitness_distance(creatures: &Vec<Creature>) -> Vec<f64> {
+// keeps creatures that barely move their muscles from dividing by nothing
+const MIN_WORK: f64 = 1.0;
+
+pub fn from_config(config: FitnessConfig) -> FitnessFunction {
+    match config {
+        FitnessConfig::Distance => Box::new(fitness_distance),
+        FitnessConfig::Efficiency => Box::new(fitness_efficiency),
+        FitnessConfig::Metabolic { cost } => Box::new(move |creatures| fitness_metabolic(creatures, cost)),
+    }
+}
+
+pub fn fitness_distance(creatures: &[Creature]) -> Vec<f64> {
     creatures.iter().map(|creature| {
         let mut total: f64 = 0.0;
         for particle in &creature.particles {
@@ -13,3 +24,19 @@ pub fn fitness_distance(creatures: &Vec<Creature>) -> Vec<f64> {
     }).collect()
 }
 
+// distance travelled per unit of muscle work
+pub fn fitness_efficiency(creatures: &[Creature]) -> Vec<f64> {
+    creatures.iter().map(|creature| {
+        let distance = creature.centre_of_mass().x - creature.origin.x;
+        distance / (creature.energy.actuation_work + MIN_WORK)
+    }).collect()
+}
+
+// distance travelled with the muscle work charged against it at `cost` per
+// unit
+pub fn fitness_metabolic(creatures: &[Creature], cost: f64) -> Vec<f64> {
+    creatures.iter().map(|creature| {
+        let distance = creature.centre_of_mass().x - creature.origin.x;
+        distance - cost * creature.energy.actuation_work
+    }).collect()
+}
@@ -0,0 +1,125 @@
+use crate::{vec2::Vec2, config::WorldConfig, world::Terrain};
+
+// below this speed into the surface a contact doesn't bounce, otherwise
+// resting particles keep hopping on the gravity they pick up every step
+const RESTING_SPEED: f64 = 10.0;
+
+// the terrain together with the material it is made of
+pub struct Ground {
+    pub terrain: Terrain,
+    pub restitution: f64,
+    pub static_friction: f64,
+    pub kinetic_friction: f64,
+}
+
+impl Ground {
+    pub fn from_config(config: &WorldConfig) -> Ground {
+        Ground {
+            terrain: Terrain::from_config(&config.terrain, config.ground_y),
+            restitution: config.restitution,
+            static_friction: config.static_friction,
+            kinetic_friction: config.kinetic_friction,
+        }
+    }
+
+    // run after integration. a particle below the surface is moved back onto
+    // it and its velocity replaced by the post-contact velocity. friction is
+    // bounded by the normal velocity change, so it scales with how hard it
+    // hits. returns whether there was a contact
+    pub fn resolve(&self, position: &mut Vec2, velocity: &mut Vec2) -> bool {
+        let (point, normal) = match self.terrain.contact(*position) {
+            Some(contact) => contact,
+            None => return false,
+        };
+
+        *position = point;
+
+        let normal_speed = velocity.dot(normal);
+        let tangent = *velocity - normal * normal_speed;
+        let (normal_speed, normal_change) = if normal_speed < 0.0 {
+            let restitution = if -normal_speed > RESTING_SPEED { self.restitution } else { 0.0 };
+            (-restitution * normal_speed, -(1.0 + restitution) * normal_speed)
+        } else {
+            (normal_speed, 0.0)
+        };
+
+        let tangent_speed = tangent.len();
+        let tangent = if tangent_speed <= self.static_friction * normal_change {
+            tangent * 0.0
+        } else {
+            tangent * (1.0 - self.kinetic_friction * normal_change / tangent_speed)
+        };
+
+        *velocity = normal * normal_speed + tangent;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::{config::{SimulationConfig, MutationRange}, creature::Creature, world::World, dna, vec2::Vec2};
+
+    const STEPS: usize = 8000;
+
+    // a whole creature without muscles, held up above the ground
+    fn dropped_creature() -> (World, f64) {
+        let mut config = SimulationConfig::default();
+        config.mutation_config.active = MutationRange { min: 1.0, max: 1.0, chance: None };
+        config.mutation_config.toughness = MutationRange { min: 2000.0, max: 2000.0, chance: None };
+        config.mutation_config.reactivity = MutationRange { min: 0.0, max: 0.0, chance: None };
+        let size = config.creature_config.size;
+        let dna = dna::generate_dna(size * size, &config.mutation_config, &mut ChaCha8Rng::seed_from_u64(1));
+
+        let mut creature = Creature::new(config.creature_config, dna).unwrap();
+        for particle in creature.particles.iter_mut() {
+            particle.position = particle.position + Vec2 { x: 0.0, y: -100.0 };
+        }
+        let mut world = World::from_config(&config.world_config, config.physics);
+        world.add_creature(creature);
+        (world, config.timestep / config.sub_steps as f64)
+    }
+
+    // the energy EnergyStats tracks plus what the springs store
+    fn total_energy(creature: &Creature) -> f64 {
+        let elastic: f64 = creature.cells.iter().flatten()
+            .flat_map(|cell| cell.springs.iter())
+            .map(|spring| {
+                let stretch = (creature.particles[spring.a_id].position - creature.particles[spring.b_id].position).len() - spring.length;
+                0.5 * spring.k * stretch * stretch
+            })
+            .sum();
+        creature.energy.kinetic + creature.energy.potential + elastic
+    }
+
+    #[test]
+    fn a_dropped_creature_comes_to_rest() {
+        let (mut world, dt) = dropped_creature();
+        for _ in 0..STEPS - 400 {
+            world.update(dt);
+        }
+        let settled: Vec<Vec2> = world.creatures[0].particles.iter().map(|p| p.position).collect();
+        for _ in 0..400 {
+            world.update(dt);
+        }
+        for (particle, settled) in world.creatures[0].particles.iter().zip(settled) {
+            assert!((particle.position - settled).len() < 1e-3, "particle still moving at ({}, {})", particle.position.x, particle.position.y);
+        }
+    }
+
+    #[test]
+    fn total_energy_never_increases_on_impact() {
+        let (mut world, dt) = dropped_creature();
+        // the stats are only filled in by an update
+        world.update(dt);
+        let mut previous = total_energy(&world.creatures[0]);
+        for step in 1..STEPS {
+            world.update(dt);
+            let energy = total_energy(&world.creatures[0]);
+            assert!(energy <= previous + previous.abs() * 1e-12, "energy rose from {} to {} at step {}", previous, energy, step);
+            previous = energy;
+        }
+    }
+}
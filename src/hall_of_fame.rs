@@ -0,0 +1,51 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{dna::CreatureDna, evolution_controller::CreatureResult};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HallOfFameEntry {
+    pub dna: CreatureDna,
+    pub fitness: f64,
+    pub generation: u32,
+}
+
+// the best genomes seen over a whole run, best first
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HallOfFame {
+    capacity: usize,
+    entries: Vec<HallOfFameEntry>,
+}
+
+impl HallOfFame {
+    pub fn new(capacity: usize) -> HallOfFame {
+        HallOfFame {
+            capacity,
+            entries: Vec::with_capacity(capacity),
+        }
+    }
+
+    // expects results sorted by ascending fitness. elites are carried over
+    // unchanged, so a genome already in the hall keeps its first generation
+    pub fn record(&mut self, generation: u32, results: &[CreatureResult]) {
+        for (dna, fitness) in results.iter().rev().take(self.capacity) {
+            if self.entries.len() == self.capacity && self.entries.last().is_some_and(|worst| worst.fitness >= *fitness) {
+                break;
+            }
+            if self.entries.iter().any(|entry| entry.dna == *dna) {
+                continue;
+            }
+
+            let position = self.entries.partition_point(|entry| entry.fitness >= *fitness);
+            self.entries.insert(position, HallOfFameEntry {
+                dna: dna.clone(),
+                fitness: *fitness,
+                generation,
+            });
+            self.entries.truncate(self.capacity);
+        }
+    }
+
+    pub fn entries(&self) -> &[HallOfFameEntry] {
+        &self.entries
+    }
+}
@@ -0,0 +1,75 @@
+use chrono::UTC;
+
+use crate::{cli::CliOptions, evolution_controller::{EvolutionController, CreatureResult, ControllerEvent}, dna::storage};
+
+const LOG_OWNER: &str = "[headless]";
+
+pub fn run_headless(mut controller: EvolutionController, options: &CliOptions) -> Result<(), String> {
+    let config = controller.config();
+    let config_path = options.output.with_extension("config.toml");
+    config.save(&config_path)?;
+    println!("{}: wrote effective config to {}", LOG_OWNER, config_path.display());
+
+    controller.start().map_err(|err| format!("error while starting controller: {:?}", err))?;
+
+    let start = UTC::now();
+    let mut completed: u32 = 0;
+    let mut best: Option<CreatureResult> = None;
+
+    let last_results = loop {
+        let event = controller.next_event()
+            .map_err(|err| format!("error while waiting for results: {:?}", err))?;
+        let (statistics, results) = match event {
+            ControllerEvent::GenerationFinished { statistics, results } => (statistics, results),
+            ControllerEvent::Error(msg) => return Err(msg),
+            _ => continue,
+        };
+        completed += 1;
+
+        // results are sorted by ascending fitness
+        if let Some(generation_best) = results.last() {
+            println!("{}: generation {}, best {}, median {}, unstable {}", LOG_OWNER, statistics.generation, statistics.best, statistics.median, statistics.unstable);
+
+            let improved = match &best {
+                Some((_, fitness)) => generation_best.1 > *fitness,
+                None => true,
+            };
+            if improved {
+                best = Some(generation_best.clone());
+            }
+        }
+
+        let elapsed = (UTC::now() - start).num_milliseconds() as f64 / 1000.0;
+        let generations_done = options.generations.is_some_and(|limit| completed >= limit);
+        let budget_spent = options.time_budget.is_some_and(|budget| elapsed >= budget);
+        if generations_done || budget_spent {
+            println!("{}: finished {} generations in {:.1}s", LOG_OWNER, completed, elapsed);
+            break results;
+        }
+    };
+
+    // the controller writes its final checkpoint before reporting that it stopped
+    controller.stop().map_err(|err| format!("error while stopping controller: {:?}", err))?;
+    loop {
+        let event = controller.next_event()
+            .map_err(|err| format!("error while stopping controller: {:?}", err))?;
+        if let ControllerEvent::Stopped { .. } = event {
+            break;
+        }
+    }
+
+    let size = config.creature_config.size;
+    let population_path = options.output.with_extension("population.json");
+    storage::save_population(&population_path, &last_results, size)?;
+    println!("{}: wrote final population to {}", LOG_OWNER, population_path.display());
+
+    for (i, entry) in controller.hall_of_fame().iter().enumerate() {
+        println!("{}: hall of fame #{}: fitness {} from generation {}", LOG_OWNER, i + 1, entry.fitness, entry.generation);
+    }
+
+    let (dna, fitness) = best.ok_or("no generations completed")?;
+    storage::save_genome(&options.output, &dna, size)?;
+
+    println!("{}: wrote best creature (fitness {}) to {}", LOG_OWNER, fitness, options.output.display());
+    Ok(())
+}
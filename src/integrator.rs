@@ -0,0 +1,164 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{creature::Creature, vec2::Vec2};
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Integrator {
+    // velocity verlet, two force evaluations per step
+    Verlet,
+    // one force evaluation per step
+    SemiImplicitEuler,
+    // four force evaluations per step, the most accurate for smooth motion
+    Rk4,
+    // springs become compliant distance constraints, stable at any stiffness
+    Xpbd { iterations: u32 },
+}
+
+// working space for the integrators, kept from step to step so stepping
+// doesn't allocate
+#[derive(Default)]
+pub struct Scratch {
+    accelerations: Vec<Vec2>,
+    stage_positions: Vec<Vec2>,
+    stage_velocities: Vec<Vec2>,
+    position_sum: Vec<Vec2>,
+    velocity_sum: Vec<Vec2>,
+    start: Vec<Vec2>,
+    lambdas: Vec<f64>,
+}
+
+// advances `positions` and `velocities`, which start out as the creature's
+// current state, by `dt`. the creature itself isn't touched so the step can
+// be thrown away and retried
+pub fn step(integrator: Integrator, creature: &Creature, positions: &mut [Vec2], velocities: &mut [Vec2], scratch: &mut Scratch, dt: f64) {
+    match integrator {
+        Integrator::Verlet => verlet(creature, positions, velocities, scratch, dt),
+        Integrator::SemiImplicitEuler => semi_implicit_euler(creature, positions, velocities, scratch, dt),
+        Integrator::Rk4 => rk4(creature, positions, velocities, scratch, dt),
+        Integrator::Xpbd { iterations } => xpbd(creature, positions, velocities, scratch, iterations, dt),
+    }
+
+    // exponential decay so the damping doesn't depend on the step size.
+    // particles almost always share their damping, so the last factor is kept
+    let mut decay = (f64::NAN, 1.0);
+    for (velocity, particle) in velocities.iter_mut().zip(creature.particles.iter()) {
+        if particle.damping != decay.0 {
+            decay = (particle.damping, (-particle.damping * dt).exp());
+        }
+        *velocity = *velocity * decay.1;
+    }
+}
+
+fn zeroed(buffer: &mut Vec<Vec2>, length: usize) -> &mut [Vec2] {
+    buffer.clear();
+    buffer.resize(length, Vec2 { x: 0.0, y: 0.0 });
+    buffer
+}
+
+fn copied<'a>(buffer: &'a mut Vec<Vec2>, values: &[Vec2]) -> &'a mut [Vec2] {
+    buffer.clear();
+    buffer.extend_from_slice(values);
+    buffer
+}
+
+fn semi_implicit_euler(creature: &Creature, positions: &mut [Vec2], velocities: &mut [Vec2], scratch: &mut Scratch, dt: f64) {
+    let accelerations = zeroed(&mut scratch.accelerations, positions.len());
+    creature.accelerations(positions, velocities, accelerations);
+
+    for ((position, velocity), acceleration) in positions.iter_mut().zip(velocities.iter_mut()).zip(accelerations.iter()) {
+        *velocity = *velocity + *acceleration * dt;
+        *position = *position + *velocity * dt;
+    }
+}
+
+// kick, drift, kick. the second force evaluation uses the half step velocity
+// since the spring damping depends on velocity too
+fn verlet(creature: &Creature, positions: &mut [Vec2], velocities: &mut [Vec2], scratch: &mut Scratch, dt: f64) {
+    let accelerations = zeroed(&mut scratch.accelerations, positions.len());
+    creature.accelerations(positions, velocities, accelerations);
+    for ((position, velocity), acceleration) in positions.iter_mut().zip(velocities.iter_mut()).zip(accelerations.iter()) {
+        *velocity = *velocity + *acceleration * (dt * 0.5);
+        *position = *position + *velocity * dt;
+    }
+
+    creature.accelerations(positions, velocities, accelerations);
+    for (velocity, acceleration) in velocities.iter_mut().zip(accelerations.iter()) {
+        *velocity = *velocity + *acceleration * (dt * 0.5);
+    }
+}
+
+fn rk4(creature: &Creature, positions: &mut [Vec2], velocities: &mut [Vec2], scratch: &mut Scratch, dt: f64) {
+    let count = positions.len();
+    let stage_positions = copied(&mut scratch.stage_positions, positions);
+    let stage_velocities = copied(&mut scratch.stage_velocities, velocities);
+    let position_sum = zeroed(&mut scratch.position_sum, count);
+    let velocity_sum = zeroed(&mut scratch.velocity_sum, count);
+    let accelerations = zeroed(&mut scratch.accelerations, count);
+
+    // each stage is evaluated at the state reached by the previous stage's
+    // derivative, then weighted 1, 2, 2, 1 into the final step
+    for (stage_step, weight) in [(0.5, 1.0), (0.5, 2.0), (1.0, 2.0), (0.0, 1.0)] {
+        creature.accelerations(stage_positions, stage_velocities, accelerations);
+
+        for i in 0..count {
+            let velocity = stage_velocities[i];
+            position_sum[i] = position_sum[i] + velocity * weight;
+            velocity_sum[i] = velocity_sum[i] + accelerations[i] * weight;
+
+            stage_positions[i] = positions[i] + velocity * (dt * stage_step);
+            stage_velocities[i] = velocities[i] + accelerations[i] * (dt * stage_step);
+        }
+    }
+
+    for i in 0..count {
+        positions[i] = positions[i] + position_sum[i] * (dt / 6.0);
+        velocities[i] = velocities[i] + velocity_sum[i] * (dt / 6.0);
+    }
+}
+
+// extended position based dynamics: external accelerations predict the new
+// positions, then each spring is projected towards its rest length with a
+// compliance of 1 / k and its damping, and the velocity is whatever movement
+// is left. `max_force` has no meaning here and is ignored
+fn xpbd(creature: &Creature, positions: &mut [Vec2], velocities: &mut [Vec2], scratch: &mut Scratch, iterations: u32, dt: f64) {
+    let start = copied(&mut scratch.start, positions);
+    for ((position, velocity), particle) in positions.iter_mut().zip(velocities.iter_mut()).zip(creature.particles.iter()) {
+        *velocity = *velocity + particle.acceleration * dt;
+        *position = *position + *velocity * dt;
+    }
+
+    let springs = || creature.springs().filter(|spring| spring.k > 0.0);
+    let lambdas = &mut scratch.lambdas;
+    lambdas.clear();
+    lambdas.resize(springs().count(), 0.0);
+    for _ in 0..iterations {
+        for (spring, lambda) in springs().zip(lambdas.iter_mut()) {
+            let (a, b) = (spring.a_id, spring.b_id);
+            let dir = positions[a] - positions[b];
+            let dist = dir.len();
+            if dist == 0.0 {
+                continue;
+            }
+            let normal = dir / dist;
+
+            let weight_a = 1.0 / creature.particles[a].mass;
+            let weight_b = 1.0 / creature.particles[b].mass;
+            let compliance = 1.0 / (spring.k * dt * dt);
+            let gamma = spring.damping / (spring.k * dt);
+            let separation = normal.dot((positions[a] - start[a]) - (positions[b] - start[b]));
+
+            let constraint = dist - spring.length;
+            let delta = (-constraint - compliance * *lambda - gamma * separation)
+                / ((1.0 + gamma) * (weight_a + weight_b) + compliance);
+            *lambda += delta;
+
+            positions[a] = positions[a] + normal * (weight_a * delta);
+            positions[b] = positions[b] - normal * (weight_b * delta);
+        }
+    }
+
+    for ((velocity, position), start) in velocities.iter_mut().zip(positions.iter()).zip(start.iter()) {
+        *velocity = (*position - *start) / dt;
+    }
+}
@@ -1,15 +1,16 @@
 use config::SimulationConfig;
 use creature::Creature;
-use dna::CreatureDna;
-use evolution_controller::EvolutionController;
-use fitness::fitness_distance;
+use dna::{CreatureDna, storage};
+use evolution_controller::{EvolutionController, CreatureResult, ControllerCommand, ControllerEvent};
 use glutin_window::GlutinWindow;
 use opengl_graphics::{GlGraphics, OpenGL};
 use piston::{RenderArgs, UpdateArgs, EventSettings, WindowSettings, Events, RenderEvent, UpdateEvent, ButtonEvent, Key, ButtonState, ButtonArgs, Button};
-use renderers::{solid::render_solid, RenderPass};
+use renderers::{solid::render_solid, wireframe::render_wireframe, RenderPass};
 use statistics::{StatisticsPanel, fitness_chart::FitnessChart};
 use vec2::Vec2;
 use world::World;
+use cli::CliOptions;
+use std::{env, process, path::{Path, PathBuf}};
 
 extern crate chrono;
 
@@ -19,6 +20,12 @@ mod spring;
 mod cell;
 mod creature;
 mod world;
+mod ground;
+mod collision;
+mod integrator;
+mod sensor;
+mod neural;
+mod energy;
 mod renderers;
 mod dna;
 mod charge;
@@ -27,6 +34,11 @@ mod simulator;
 mod fitness;
 mod evolution_controller;
 mod statistics;
+mod cli;
+mod headless;
+mod checkpoint;
+mod selection;
+mod hall_of_fame;
 
 const LOG_OWNER: &str = "[main]";
 
@@ -35,15 +47,18 @@ pub struct App {
     world: World,
     sub_steps: i32,
     render_passes: Vec<RenderPass>,
+    wireframe: bool,
     config: SimulationConfig,
     evolution_controller: EvolutionController,
     statistics_panels: Vec<Box<dyn StatisticsPanel>>,
+    output: PathBuf,
+    progress: f64,
 }
 
 impl App {
-    fn from_config(config: SimulationConfig, gl: GlGraphics) -> App {
-        let world = World::from_config(config.world_config);
-        let fitness = Box::new(fitness_distance);
+    fn new(evolution_controller: EvolutionController, gl: GlGraphics, output: PathBuf) -> App {
+        let config = evolution_controller.config();
+        let world = World::from_config(&config.world_config, config.physics);
         let percentiles: Vec<f64> = vec![25.0, 50.0, 75.0, 100.0];
 
         App {
@@ -55,11 +70,14 @@ impl App {
                     render_solid(world, args, gl)
                 })
             ],
+            wireframe: false,
             config,
-            evolution_controller: EvolutionController::new(config, fitness),
+            evolution_controller,
             statistics_panels: vec![
                 Box::new(FitnessChart::new(percentiles, 20.0))
             ],
+            output,
+            progress: 0.0,
         }
     }
 
@@ -86,6 +104,13 @@ impl App {
 
             panel.render(args.viewport(), &mut self.gl, position, size);
         }
+
+        if self.progress > 0.0 {
+            let bar = [0.0, 0.0, args.window_size[0] * self.progress, 4.0];
+            self.gl.draw(args.viewport(), |c, gl| {
+                graphics::rectangle([0.3, 0.6, 1.0, 1.0], bar, c.transform, gl);
+            });
+        }
     }
 
     fn update(&mut self, args: &UpdateArgs) {
@@ -94,76 +119,183 @@ impl App {
             self.world.update(dt);
         }
 
-        let results = self.evolution_controller.try_get_results();
-        if results.len() > 0 {
-            for panel in self.statistics_panels.iter_mut() {
-                panel.gather_statistics(&results);
+        for event in self.evolution_controller.poll_events() {
+            match event {
+                ControllerEvent::GenerationStarted { generation } => println!("{}: simulating generation {}", LOG_OWNER, generation),
+                ControllerEvent::Progress { steps, total_steps } => {
+                    self.progress = steps as f64 / total_steps.max(1) as f64;
+                },
+                ControllerEvent::Paused => {
+                    self.progress = 0.0;
+                    println!("{}: controller paused", LOG_OWNER);
+                },
+                ControllerEvent::GenerationFinished { results, .. } => {
+                    self.progress = 0.0;
+                    for panel in self.statistics_panels.iter_mut() {
+                        panel.gather_statistics(&results);
+                    }
+                },
+                ControllerEvent::Stopped { results } => {
+                    self.progress = 0.0;
+                    self.preview_results(results);
+                },
+                ControllerEvent::Error(msg) => eprintln!("{}: controller error: {}", LOG_OWNER, msg),
             }
         }
     }
 
-    fn start_controller(&mut self) {
-        let result = self.evolution_controller.start();
+    fn send_command(&mut self, command: ControllerCommand) {
+        let result = match command {
+            ControllerCommand::Start => self.evolution_controller.start(),
+            ControllerCommand::Pause => self.evolution_controller.pause(),
+            ControllerCommand::Step => self.evolution_controller.step(),
+            ControllerCommand::Cancel => self.evolution_controller.cancel(),
+            ControllerCommand::Stop => self.evolution_controller.stop(),
+            ControllerCommand::Shutdown => self.evolution_controller.shutdown(),
+        };
         if let Err(msg) = result {
-            eprintln!("{}: error while starting controller: {:?}", LOG_OWNER, msg);
+            eprintln!("{}: error while sending command to controller: {:?}", LOG_OWNER, msg);
         }
     }
 
-    fn stop_controller(&mut self) {
-        match self.evolution_controller.stop() {
-            Ok(results) => {
-                self.world.reset();
-                println!("{}: controller stopped, previewing...", LOG_OWNER);
-
-                let first_result = results.last();
-                if let Some((preview, fitness)) = first_result {
-                    println!("{}: previewing best creature out of {}, fitness: {}", LOG_OWNER, results.len(), fitness);
-                    let creature = Creature::new(self.config.creature_config, preview.clone());
-                    if let Some(creature) = creature {
-                        self.world.add_creature(creature);
-                    }
-                }
+    fn preview_results(&mut self, results: Vec<CreatureResult>) {
+        self.world.reset();
+        println!("{}: controller stopped, previewing...", LOG_OWNER);
+        self.save_results(&results);
+
+        let first_result = results.last();
+        if let Some((preview, fitness)) = first_result {
+            println!("{}: previewing best creature out of {}, fitness: {}", LOG_OWNER, results.len(), fitness);
+            self.set_creatures(vec![preview.clone()]);
+        }
+    }
+
+    fn save_results(&self, results: &[CreatureResult]) {
+        let size = self.config.creature_config.size;
+        let config_path = self.output.with_extension("config.toml");
+        let population_path = self.output.with_extension("population.json");
+
+        let saved = self.config.save(&config_path)
+            .and_then(|_| storage::save_population(&population_path, results, size))
+            .and_then(|_| match results.last() {
+                Some((best, _)) => storage::save_genome(&self.output, best, size),
+                None => Ok(()),
+            });
+
+        match saved {
+            Ok(_) => println!("{}: saved results to {}", LOG_OWNER, self.output.display()),
+            Err(msg) => eprintln!("{}: error while saving results: {}", LOG_OWNER, msg),
+        }
+    }
+
+    pub fn load_creatures(&mut self, path: &Path) {
+        match storage::load_genomes(path, self.config.creature_config) {
+            Ok(dna) => {
+                println!("{}: previewing {} creatures from {}", LOG_OWNER, dna.len(), path.display());
+                self.set_creatures(dna);
             },
-            Err(msg) => {
-                eprintln!("{}: error while stopping controller: {:?}", LOG_OWNER, msg);
-            }
+            Err(msg) => eprintln!("{}: error while loading creatures: {}", LOG_OWNER, msg),
         }
     }
 
     fn handle_input(&mut self, args: &ButtonArgs) {
+        if args.state != ButtonState::Press {
+            return;
+        }
+
         if let Button::Keyboard(key) = args.button {
-            if key == Key::Space && args.state == ButtonState::Press {
-                if self.evolution_controller.is_running() {
-                    self.stop_controller();
-                } else {
-                    self.start_controller();
-                }
+            match key {
+                Key::Space => {
+                    if self.evolution_controller.is_running() {
+                        self.send_command(ControllerCommand::Stop);
+                    } else {
+                        self.send_command(ControllerCommand::Start);
+                    }
+                },
+                Key::P => self.send_command(ControllerCommand::Pause),
+                Key::N => self.send_command(ControllerCommand::Step),
+                Key::C => self.send_command(ControllerCommand::Cancel),
+                Key::W => self.toggle_wireframe(),
+                _ => {
+                    if let Some(index) = hall_of_fame_index(key) {
+                        self.preview_hall_of_fame(index);
+                    }
+                },
             }
         }
-        match args.button {
-            piston::Button::Keyboard(key) => {
-                if key == Key::Space && args.state == ButtonState::Press {
-                }
+    }
+
+    fn preview_hall_of_fame(&mut self, index: usize) {
+        match self.evolution_controller.hall_of_fame().get(index) {
+            Some(entry) => {
+                println!("{}: previewing hall of fame #{} from generation {}, fitness: {}", LOG_OWNER, index + 1, entry.generation, entry.fitness);
+                self.set_creatures(vec![entry.dna.clone()]);
             },
-            _ => {}
+            None => println!("{}: no hall of fame entry #{}", LOG_OWNER, index + 1),
         }
     }
 
+    fn toggle_wireframe(&mut self) {
+        self.wireframe = !self.wireframe;
+        self.render_passes = if self.wireframe {
+            vec![Box::new(render_wireframe)]
+        } else {
+            vec![Box::new(render_solid)]
+        };
+    }
+
     pub fn set_creatures(&mut self, dna: Vec<CreatureDna>) {
         let creatures = dna.iter().map(|dna| {
             Creature::new(self.config.creature_config, dna.clone())
         });
 
         self.world.reset();
-        for creature in creatures {
-            if let Some(creature) = creature {
-                self.world.add_creature(creature);
-            }
+        for creature in creatures.flatten() {
+            self.world.add_creature(creature);
         }
     }
 }
 
+fn hall_of_fame_index(key: Key) -> Option<usize> {
+    let keys = [Key::D1, Key::D2, Key::D3, Key::D4, Key::D5, Key::D6, Key::D7, Key::D8, Key::D9];
+    keys.iter().position(|k| *k == key)
+}
+
+fn create_controller(options: &CliOptions) -> Result<EvolutionController, String> {
+    match &options.resume {
+        Some(path) => EvolutionController::resume(path, options.checkpoint.clone()),
+        None => {
+            let config = SimulationConfig::load(options.config_path.as_deref(), &options.overrides)?;
+            Ok(EvolutionController::new(config, options.checkpoint.clone()))
+        },
+    }
+}
+
 fn main() {
+    let options = match cli::parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            process::exit(2);
+        }
+    };
+
+    let controller = match create_controller(&options) {
+        Ok(controller) => controller,
+        Err(msg) => {
+            eprintln!("{}: {}", LOG_OWNER, msg);
+            process::exit(2);
+        }
+    };
+
+    if options.headless {
+        if let Err(msg) = headless::run_headless(controller, &options) {
+            eprintln!("{}: {}", LOG_OWNER, msg);
+            process::exit(1);
+        }
+        return;
+    }
+
     let opengl = OpenGL::V2_1;
 
     let mut window: GlutinWindow = WindowSettings::new("evolution simulator", [1920, 1080])
@@ -172,8 +304,10 @@ fn main() {
         .build()
         .unwrap();
 
-    let config = SimulationConfig::default();
-    let mut app = App::from_config(config, GlGraphics::new(opengl));
+    let mut app = App::new(controller, GlGraphics::new(opengl), options.output.clone());
+    if let Some(path) = &options.load {
+        app.load_creatures(path);
+    }
 
     let mut events = Events::new(EventSettings::new());
     while let Some(e) = events.next(&mut window) {
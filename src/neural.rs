@@ -0,0 +1,138 @@
+// a continuous time recurrent network with one unit per cell, as an
+// alternative to charges spreading between cells. each unit leaks towards a
+// weighted sum of its own output, its four neighbours' outputs, the sensor
+// readings at the cell and a global clock, and its output replaces the charge
+// that drives the cell's muscles. the weights are genes of the unit's cell
+use std::f64::consts::TAU;
+
+use crate::{cell::Cell, sensor::{self, Senses, SensorKind}};
+
+pub const WEIGHT_COUNT: usize = 12;
+
+// where each input's weight sits in `CellDna::weights`
+const SELF_WEIGHT: usize = 0;
+// above, below, left and right
+const NEIGHBOUR_WEIGHTS: usize = 1;
+// ground contact, tilt, velocity and stretch
+const SENSOR_WEIGHTS: usize = 5;
+// sine and cosine of the clock
+const CLOCK_WEIGHTS: usize = 9;
+const BIAS_WEIGHT: usize = 11;
+
+pub struct NeuralController {
+    size: usize,
+    // one per grid position, units of missing cells stay at 0
+    states: Vec<f64>,
+    next_states: Vec<f64>,
+    // seconds for a unit to cover most of the way to its input
+    time_constant: f64,
+    clock_frequency: f64,
+    time: f64,
+}
+
+impl NeuralController {
+    pub fn new(size: usize, time_constant: f64, clock_frequency: f64) -> NeuralController {
+        NeuralController {
+            size,
+            states: vec![0.0; size * size],
+            next_states: vec![0.0; size * size],
+            time_constant,
+            clock_frequency,
+            time: 0.0,
+        }
+    }
+
+    // between -1 and 1, in place of the cell's charge
+    pub fn output(&self, pos: (usize, usize)) -> f64 {
+        let (row, col) = pos;
+        self.states[row * self.size + col].tanh()
+    }
+
+    fn neighbour_output(&self, row: usize, col: usize, offset: (isize, isize)) -> f64 {
+        let (row, col) = (row as isize + offset.0, col as isize + offset.1);
+        if row < 0 || col < 0 || row >= self.size as isize || col >= self.size as isize {
+            return 0.0;
+        }
+        self.output((row as usize, col as usize))
+    }
+
+    // advances every unit by `dt`
+    pub fn update(&mut self, cells: &[Option<Cell>], senses: &Senses, dt: f64) {
+        let tilt = sensor::read(SensorKind::Tilt, 1.0, senses, [0; 4], &[]);
+        let velocity = sensor::read(SensorKind::Velocity, 1.0, senses, [0; 4], &[]);
+        let clock = TAU * self.clock_frequency * self.time;
+        // exact for an input held over the step, so any step size is stable
+        let blend = 1.0 - (-dt / self.time_constant).exp();
+
+        for cell in cells.iter().flatten() {
+            let weights = &cell.dna.weights;
+            let (row, col) = cell.pos;
+            let neighbours = [(-1, 0), (1, 0), (0, -1), (0, 1)].map(|offset| self.neighbour_output(row, col, offset));
+            let sensors = [
+                sensor::read(SensorKind::GroundContact, 1.0, senses, cell.corners, &[]),
+                tilt,
+                velocity,
+                sensor::read(SensorKind::Stretch, 1.0, senses, cell.corners, &cell.springs),
+            ];
+
+            let mut input = weights[SELF_WEIGHT] * self.output(cell.pos)
+                + weights[CLOCK_WEIGHTS] * clock.sin()
+                + weights[CLOCK_WEIGHTS + 1] * clock.cos()
+                + weights[BIAS_WEIGHT];
+            for (i, output) in neighbours.iter().enumerate() {
+                input += weights[NEIGHBOUR_WEIGHTS + i] * output;
+            }
+            for (i, reading) in sensors.iter().enumerate() {
+                input += weights[SENSOR_WEIGHTS + i] * reading;
+            }
+
+            let state = self.states[row * self.size + col];
+            self.next_states[row * self.size + col] = state + (input - state) * blend;
+        }
+
+        std::mem::swap(&mut self.states, &mut self.next_states);
+        self.time += dt;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::{config::SimulationConfig, creature::Creature, dna, sensor::Senses};
+
+    use super::{NeuralController, WEIGHT_COUNT, BIAS_WEIGHT, NEIGHBOUR_WEIGHTS};
+
+    #[test]
+    fn units_settle_on_their_weighted_inputs() {
+        let config = SimulationConfig::default();
+        let size = config.creature_config.size;
+        let mut dna = dna::generate_dna(size * size, &config.mutation_config, &mut ChaCha8Rng::seed_from_u64(0));
+        for cell in dna.iter_mut() {
+            cell.active = 1.0;
+            cell.weights = [0.0; WEIGHT_COUNT];
+        }
+        // the top left unit is biased, its right hand neighbour follows it
+        dna[0].weights[BIAS_WEIGHT] = 2.0;
+        dna[1].weights[NEIGHBOUR_WEIGHTS + 2] = 1.0;
+        let creature = Creature::new(config.creature_config, dna).unwrap();
+
+        let mut network = NeuralController::new(size, 0.1, 1.0);
+        let senses = Senses { particles: &creature.particles, size };
+        for _ in 0..800 {
+            network.update(&creature.cells, &senses, 0.0025);
+        }
+
+        let (leader, follower) = (network.output((0, 0)), network.output((0, 1)));
+        assert!((leader - 2.0_f64.tanh()).abs() < 1e-3, "leader settled on {}", leader);
+        assert!((follower - leader.tanh()).abs() < 1e-3, "follower settled on {}", follower);
+        for row in 0..size {
+            for col in 0..size {
+                if row > 0 || col > 1 {
+                    assert_eq!(network.output((row, col)), 0.0, "unit {} {} moved", row, col);
+                }
+            }
+        }
+    }
+}
@@ -1,27 +1,20 @@
 use crate::vec2::Vec2;
 
+#[derive(Clone, Copy)]
 pub struct Particle {
     pub position: Vec2,
-    pub old_position: Vec2,
+    pub velocity: Vec2,
+    // external acceleration for the coming step, cleared once it is taken
     pub acceleration: Vec2,
     pub mass: f64,
+    // exponential decay rate of the velocity, per second
     pub damping: f64,
+    // touched the ground at the end of the last step
+    pub grounded: bool,
 }
 
 impl Particle {
-    pub fn integrate(&mut self, dt: f64) {
-        let velocity = self.position - self.old_position;
-        self.old_position = self.position;
-        self.position = self.position + velocity * (1.0 - self.damping) + self.acceleration * dt * dt;
-        self.acceleration = Vec2 { x: 0.0, y: 0.0 };
-    }
-
     pub fn accelerate(&mut self, acceleration: Vec2) {
         self.acceleration = self.acceleration + acceleration;
     }
-
-    pub fn add_force(&mut self, force: Vec2) {
-        self.accelerate(force / self.mass)
-    }
 }
-
@@ -7,4 +7,5 @@ pub type RenderPass = Box<dyn Fn(&World, &RenderArgs, &mut GlGraphics)>;
 
 pub mod wireframe;
 pub mod solid;
+pub mod terrain;
 
@@ -1,17 +1,22 @@
 use opengl_graphics::GlGraphics;
 use piston::RenderArgs;
 
-use crate::{world::World, creature::Creature, charge::ChargeModel, cell::Cell};
+use crate::{world::World, creature::Creature, cell::Cell};
+
+use super::terrain::render_terrain_solid;
 
 fn get_position(row: usize, col: usize, creature: &Creature) -> [f64; 2] {
     let position = creature.particles[Creature::get_cell_id(row, col, creature.size + 1)].position;
     [position.x, position.y]
 }
 
-fn get_color(cell: &Cell) -> [f32; 4] {
-    let charge = cell.charge_model.get_charge() as f32 * 0.2;
+fn get_color(creature: &Creature, cell: &Cell) -> [f32; 4] {
+    let charge = Creature::signal(creature.controller.as_ref(), cell) as f32 * 0.2;
     let toughness = cell.dna.toughness as f32 / 2000.0;
     let conductivity = cell.dna.conductivity as f32 * 0.5;
+    if cell.damaged {
+        return [0.5 + charge, 0.1, 0.1, 1.0];
+    }
 
     [
         toughness * conductivity + charge * 2.0,
@@ -28,12 +33,13 @@ pub fn render_solid(world: &World, args: &RenderArgs, gl: &mut GlGraphics) {
     gl.draw(args.viewport(), |_, gl| {
         clear(BG, gl);
     });
+    render_terrain_solid(world, args, gl);
 
     for creature in world.creatures.iter() {
         for row in 0..creature.size {
             for col in 0..creature.size {
                 if let Some(cell) = &creature.cells[Creature::get_cell_id(row, col, creature.size)] {
-                    let color = get_color(&cell);
+                    let color = get_color(creature, cell);
 
                     let points = [
                         get_position(row, col, creature),
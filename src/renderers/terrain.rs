@@ -0,0 +1,42 @@
+use opengl_graphics::GlGraphics;
+use piston::RenderArgs;
+
+use crate::world::World;
+
+const GROUND_COLOR: [f32; 4] = [0.25, 0.22, 0.2, 1.0];
+const OUTLINE_COLOR: [f32; 4] = [0.6, 0.6, 0.6, 1.0];
+
+// fills the ground down to the bottom of the window, one quad per segment
+// since the polygon as a whole is rarely convex
+pub fn render_terrain_solid(world: &World, args: &RenderArgs, gl: &mut GlGraphics) {
+    use graphics::*;
+
+    let bottom = args.window_size[1];
+    for segment in world.ground.terrain.points().windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+        if a.x == b.x {
+            continue;
+        }
+
+        let quad = [
+            [a.x, a.y],
+            [b.x, b.y],
+            [b.x, bottom.max(b.y)],
+            [a.x, bottom.max(a.y)],
+        ];
+        gl.draw(args.viewport(), |c, gl| {
+            polygon(GROUND_COLOR, &quad, c.transform, gl);
+        });
+    }
+}
+
+pub fn render_terrain_outline(world: &World, args: &RenderArgs, gl: &mut GlGraphics) {
+    use graphics::*;
+
+    for segment in world.ground.terrain.points().windows(2) {
+        let points = [segment[0].x, segment[0].y, segment[1].x, segment[1].y];
+        gl.draw(args.viewport(), |c, gl| {
+            line(OUTLINE_COLOR, 1.0, points, c.transform, gl);
+        });
+    }
+}
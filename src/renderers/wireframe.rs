@@ -3,6 +3,8 @@ use piston::RenderArgs;
 
 use crate::{world::World, spring::Spring};
 
+use super::terrain::render_terrain_outline;
+
 pub fn render_wireframe(world: &World, args: &RenderArgs, gl: &mut GlGraphics) {
     use graphics::*;
 
@@ -10,6 +12,7 @@ pub fn render_wireframe(world: &World, args: &RenderArgs, gl: &mut GlGraphics) {
     gl.draw(args.viewport(), |_, gl| {
         clear(BG, gl);
     });
+    render_terrain_outline(world, args, gl);
 
     let color: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
     let square = rectangle::square(0.0, 0.0, 8.0);
@@ -25,13 +28,7 @@ pub fn render_wireframe(world: &World, args: &RenderArgs, gl: &mut GlGraphics) {
         }
 
         let springs: Vec<&Spring> = creature.cells.iter()
-            .filter_map(|cell| {
-                if let Some(cell) = cell {
-                    Some(cell)
-                } else {
-                    None
-                }
-            })
+            .flatten()
             .flat_map(|cell| {
                 &cell.springs
             })
@@ -40,10 +37,10 @@ pub fn render_wireframe(world: &World, args: &RenderArgs, gl: &mut GlGraphics) {
         for spring in springs {
             gl.draw(args.viewport(), |c, gl| {
                 let points = [
-                    creature.particles[spring.a_id].position.x.clone(),
-                    creature.particles[spring.a_id].position.y.clone(),
-                    creature.particles[spring.b_id].position.x.clone(),
-                    creature.particles[spring.b_id].position.y.clone(),
+                    creature.particles[spring.a_id].position.x,
+                    creature.particles[spring.a_id].position.y,
+                    creature.particles[spring.b_id].position.x,
+                    creature.particles[spring.b_id].position.y,
                 ];
 
                 line(color, 1.0, points, c.transform, gl);
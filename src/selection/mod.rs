@@ -0,0 +1,70 @@
+use rand::{Rng, RngCore};
+
+use crate::{config::SelectionConfig, dna::CreatureDna, evolution_controller::CreatureResult};
+
+pub mod triangular;
+pub mod tournament;
+pub mod truncation;
+pub mod roulette;
+pub mod rank;
+pub mod stochastic_universal;
+
+pub trait SelectionStrategy {
+    // `results` are sorted by ascending fitness, returns `count` parents which
+    // each produce one child
+    fn select(&self, results: &[CreatureResult], count: usize, rng: &mut dyn RngCore) -> Vec<CreatureDna>;
+}
+
+pub fn from_config(config: SelectionConfig) -> Box<dyn SelectionStrategy + Send> {
+    match config {
+        SelectionConfig::Triangular => Box::new(triangular::Triangular),
+        SelectionConfig::Tournament { size } => Box::new(tournament::Tournament::new(size)),
+        SelectionConfig::Truncation { fraction } => Box::new(truncation::Truncation::new(fraction)),
+        SelectionConfig::Roulette => Box::new(roulette::Roulette),
+        SelectionConfig::Rank { pressure } => Box::new(rank::Rank::new(pressure)),
+        SelectionConfig::StochasticUniversal => Box::new(stochastic_universal::StochasticUniversal),
+    }
+}
+
+// repeats the survivors in order until there are `count` of them, so every
+// survivor gets an equal share of the offspring
+fn fill_from(survivors: &[CreatureDna], count: usize) -> Vec<CreatureDna> {
+    if survivors.is_empty() {
+        return Vec::new();
+    }
+
+    (0..count).map(|i| {
+        survivors[i * survivors.len() / count].clone()
+    }).collect()
+}
+
+// fitness can be negative, so weights are shifted to start just above zero
+fn shifted_fitness(results: &[CreatureResult]) -> Vec<f64> {
+    let lowest = results.iter().map(|(_, fitness)| *fitness).fold(f64::INFINITY, f64::min);
+    results.iter().map(|(_, fitness)| fitness - lowest + 1e-6).collect()
+}
+
+fn cumulative(weights: &[f64]) -> Vec<f64> {
+    let mut total = 0.0;
+    weights.iter().map(|weight| {
+        total += weight;
+        total
+    }).collect()
+}
+
+fn pick(cumulative: &[f64], target: f64) -> usize {
+    cumulative.partition_point(|total| *total <= target).min(cumulative.len() - 1)
+}
+
+fn sample_weighted(results: &[CreatureResult], weights: &[f64], count: usize, rng: &mut dyn RngCore) -> Vec<CreatureDna> {
+    let cumulative = cumulative(weights);
+    let total = match cumulative.last() {
+        Some(total) => *total,
+        None => return Vec::new(),
+    };
+
+    (0..count).map(|_| {
+        let id = pick(&cumulative, rng.gen::<f64>() * total);
+        results[id].0.clone()
+    }).collect()
+}
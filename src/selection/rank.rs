@@ -0,0 +1,29 @@
+use rand::RngCore;
+
+use crate::{dna::CreatureDna, evolution_controller::CreatureResult};
+
+use super::{SelectionStrategy, sample_weighted};
+
+// linear ranking, `pressure` is the expected number of offspring of the best
+// creature, between 1.0 (no pressure) and 2.0
+pub struct Rank {
+    pressure: f64,
+}
+
+impl Rank {
+    pub fn new(pressure: f64) -> Rank {
+        Rank { pressure }
+    }
+}
+
+impl SelectionStrategy for Rank {
+    fn select(&self, results: &[CreatureResult], count: usize, rng: &mut dyn RngCore) -> Vec<CreatureDna> {
+        let n = results.len() as f64;
+        let weights: Vec<f64> = (0..results.len()).map(|rank| {
+            let position = if n > 1.0 { rank as f64 / (n - 1.0) } else { 1.0 };
+            2.0 - self.pressure + 2.0 * (self.pressure - 1.0) * position
+        }).collect();
+
+        sample_weighted(results, &weights, count, rng)
+    }
+}
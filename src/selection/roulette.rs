@@ -0,0 +1,15 @@
+use rand::RngCore;
+
+use crate::{dna::CreatureDna, evolution_controller::CreatureResult};
+
+use super::{SelectionStrategy, sample_weighted, shifted_fitness};
+
+// fitness-proportional selection
+pub struct Roulette;
+
+impl SelectionStrategy for Roulette {
+    fn select(&self, results: &[CreatureResult], count: usize, rng: &mut dyn RngCore) -> Vec<CreatureDna> {
+        let weights = shifted_fitness(results);
+        sample_weighted(results, &weights, count, rng)
+    }
+}
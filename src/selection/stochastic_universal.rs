@@ -0,0 +1,26 @@
+use rand::{Rng, RngCore};
+
+use crate::{dna::CreatureDna, evolution_controller::CreatureResult};
+
+use super::{SelectionStrategy, cumulative, pick, shifted_fitness};
+
+// fitness-proportional like roulette, but with evenly spaced pointers from a
+// single spin so the number of offspring stays close to its expected value
+pub struct StochasticUniversal;
+
+impl SelectionStrategy for StochasticUniversal {
+    fn select(&self, results: &[CreatureResult], count: usize, rng: &mut dyn RngCore) -> Vec<CreatureDna> {
+        let cumulative = cumulative(&shifted_fitness(results));
+        let total = match cumulative.last() {
+            Some(total) => *total,
+            None => return Vec::new(),
+        };
+
+        let spacing = total / count as f64;
+        let start = rng.gen::<f64>() * spacing;
+        (0..count).map(|i| {
+            let id = pick(&cumulative, start + i as f64 * spacing);
+            results[id].0.clone()
+        }).collect()
+    }
+}
@@ -0,0 +1,32 @@
+use rand::{Rng, RngCore};
+
+use crate::{dna::CreatureDna, evolution_controller::CreatureResult};
+
+use super::SelectionStrategy;
+
+pub struct Tournament {
+    size: usize,
+}
+
+impl Tournament {
+    pub fn new(size: usize) -> Tournament {
+        Tournament { size }
+    }
+}
+
+impl SelectionStrategy for Tournament {
+    fn select(&self, results: &[CreatureResult], count: usize, rng: &mut dyn RngCore) -> Vec<CreatureDna> {
+        if results.is_empty() {
+            return Vec::new();
+        }
+
+        // results are sorted, so the highest index drawn is the winner
+        (0..count).map(|_| {
+            let winner = (0..self.size)
+                .map(|_| rng.gen_range(0..results.len()))
+                .max()
+                .unwrap_or(0);
+            results[winner].0.clone()
+        }).collect()
+    }
+}
@@ -0,0 +1,23 @@
+use rand::{Rng, RngCore};
+
+use crate::{dna::CreatureDna, evolution_controller::CreatureResult};
+
+use super::{SelectionStrategy, fill_from};
+
+// removes half of the population, picking victims with a triangular bias
+// towards the least fit end
+pub struct Triangular;
+
+impl SelectionStrategy for Triangular {
+    fn select(&self, results: &[CreatureResult], count: usize, rng: &mut dyn RngCore) -> Vec<CreatureDna> {
+        let mut sorted = results.to_vec();
+
+        for _ in 0..results.len() / 2 {
+            let id = (rng.gen::<f64>() - 0.5) * sorted.len() as f64;
+            sorted.remove(id.abs() as usize);
+        }
+
+        let survivors: Vec<CreatureDna> = sorted.into_iter().map(|(dna, _)| { dna }).collect();
+        fill_from(&survivors, count)
+    }
+}
@@ -0,0 +1,27 @@
+use rand::RngCore;
+
+use crate::{dna::CreatureDna, evolution_controller::CreatureResult};
+
+use super::{SelectionStrategy, fill_from};
+
+// only the fittest `fraction` of the population reproduces
+pub struct Truncation {
+    fraction: f64,
+}
+
+impl Truncation {
+    pub fn new(fraction: f64) -> Truncation {
+        Truncation { fraction }
+    }
+}
+
+impl SelectionStrategy for Truncation {
+    fn select(&self, results: &[CreatureResult], count: usize, _rng: &mut dyn RngCore) -> Vec<CreatureDna> {
+        let keep = ((results.len() as f64 * self.fraction).ceil() as usize).clamp(1, results.len().max(1));
+        let survivors: Vec<CreatureDna> = results[results.len().saturating_sub(keep)..].iter()
+            .map(|(dna, _)| dna.clone())
+            .collect();
+
+        fill_from(&survivors, count)
+    }
+}
@@ -0,0 +1,72 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{particle::Particle, spring::Spring};
+
+// readings are scaled so a typical value is around 1 before the gain
+const TILT_SCALE: f64 = std::f64::consts::FRAC_PI_4;
+const VELOCITY_SCALE: f64 = 100.0;
+const STRAIN_SCALE: f64 = 10.0;
+
+// what a sensor cell feeds its own charge model, held from step to step
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorKind {
+    #[default]
+    None,
+    // the fraction of the cell's corners touching the ground
+    GroundContact,
+    // how far the body's top edge is rotated from level, clockwise positive
+    Tilt,
+    // horizontal velocity of the centre of mass, forwards positive
+    Velocity,
+    // mean strain of the cell's own springs, stretched positive
+    Stretch,
+}
+
+// the state of one creature's particles that sensors read
+pub struct Senses<'a> {
+    pub particles: &'a [Particle],
+    // cells along one side, the particle grid has one more
+    pub size: usize,
+}
+
+impl Senses<'_> {
+    fn tilt(&self) -> f64 {
+        let edge = self.particles[self.size].position - self.particles[0].position;
+        edge.y.atan2(edge.x)
+    }
+
+    fn velocity(&self) -> f64 {
+        let total_mass: f64 = self.particles.iter().map(|particle| particle.mass).sum();
+        let momentum: f64 = self.particles.iter()
+            .map(|particle| particle.velocity.x * particle.mass)
+            .sum();
+        momentum / total_mass
+    }
+
+    fn contact(&self, corners: [usize; 4]) -> f64 {
+        corners.iter().filter(|corner| self.particles[**corner].grounded).count() as f64 / 4.0
+    }
+
+    // mean strain of `springs`
+    fn stretch(&self, springs: &[Spring]) -> f64 {
+        let total: f64 = springs.iter().map(|spring| spring.strain(self.particles)).sum();
+        if springs.is_empty() {
+            0.0
+        } else {
+            total / springs.len() as f64
+        }
+    }
+}
+
+// what a sensor on the cell with these corners and springs reads
+pub fn read(kind: SensorKind, gain: f64, senses: &Senses, corners: [usize; 4], springs: &[Spring]) -> f64 {
+    let reading = match kind {
+        SensorKind::None => return 0.0,
+        SensorKind::GroundContact => senses.contact(corners),
+        SensorKind::Tilt => senses.tilt() / TILT_SCALE,
+        SensorKind::Velocity => senses.velocity() / VELOCITY_SCALE,
+        SensorKind::Stretch => senses.stretch(springs) * STRAIN_SCALE,
+    };
+    gain * reading
+}
@@ -1,72 +1,299 @@
-use std::{sync::{mpsc::{Sender, self, Receiver}, Arc}, thread};
+use std::{sync::{mpsc::{Sender, self, Receiver}, Arc, Mutex, atomic::{AtomicBool, Ordering}}, thread::{self, JoinHandle}};
 
 use crate::{config::SimulationConfig, world::World, dna::CreatureDna, creature::Creature, fitness::FitnessFunction, evolution_controller::CreatureResult};
 
 const LOG_OWNER: &str = "[simulator]";
 
+// small enough that fast workers pick up the slack of slow ones, big enough
+// that the channel overhead doesn't matter
+const CHUNK_SIZE: usize = 16;
+
+// how many progress reports each chunk sends while it is simulated
+const PROGRESS_REPORTS: usize = 10;
+
+// a genome and its fitness, which is missing when its simulation blew up
+pub type ChunkResult = (CreatureDna, Option<f64>);
+
 pub enum SimulatorMessage {
-    Run(Vec<CreatureDna>),
-    Results(Vec<CreatureResult>),
+    Run { chunk: usize, dna: Vec<CreatureDna> },
+    Progress { chunk: usize, steps: usize },
+    Results { chunk: usize, steps: usize, results: Vec<ChunkResult> },
+    // the worker simulating the chunk panicked and is gone
+    Failed { chunk: usize },
+    Shutdown,
+}
+
+pub enum SimulationOutcome {
+    // `unstable` genomes were given the penalty fitness
+    Finished { results: Vec<CreatureResult>, unstable: usize },
+    Cancelled,
+    Failed(String),
 }
 
-pub struct Simulator {
-    message_sender: Sender<SimulatorMessage>,
-    pub message_receiver: Receiver<SimulatorMessage>,
+fn total_steps(config: &SimulationConfig) -> usize {
+    let dt = config.timestep / config.sub_steps as f64;
+    (config.sim_time / dt) as usize
 }
 
-impl Simulator {
-    pub fn from_config(config: SimulationConfig, fitness_func: Arc<FitnessFunction>) -> Simulator {
-        let mut world = World::from_config(config.world_config);
-
-        let (sim_tx, thread_rx) = mpsc::channel::<SimulatorMessage>();
-        let (thread_tx, sim_rx) = mpsc::channel::<SimulatorMessage>();
-
-        let fitness = fitness_func.clone();
-
-        thread::spawn(move || loop {
-            if let Ok(message) = thread_rx.recv() {
-                match message {
-                    SimulatorMessage::Run(all_dna) => {
-                        world.reset();
-                        for dna in all_dna.iter() {
-                            let creature = Creature::new(config.creature_config, dna.clone());
-                            if let Some(creature) = creature {
-                                world.add_creature(creature);
-                            }
-                        }
-
-                        let dt = config.timestep / config.sub_steps as f64;
-                        let total_steps = config.sim_time / dt;
-                        for _ in 0..total_steps as i32 {
-                            world.update(dt)
-                        }
-
-                        let fitnesses = fitness(&world.creatures);
-                        let results = all_dna.iter().zip(fitnesses).map(|result| {
-                            (result.0.clone(), result.1)
-                        }).collect();
-                        let result = thread_tx.send(SimulatorMessage::Results(results));
-                        if let Err(msg) = result {
-                            eprintln!("{}: error while trying to send result: {:?}", LOG_OWNER, msg);
-                        }
-                    },
-                    _ => {}
-                }
+fn send_message(sender: &Sender<SimulatorMessage>, message: SimulatorMessage) {
+    if let Err(msg) = sender.send(message) {
+        eprintln!("{}: error while trying to send message: {:?}", LOG_OWNER, msg);
+    }
+}
+
+// answers a chunk for a worker that panics while simulating it, so the pool
+// isn't left waiting on a thread that no longer exists
+struct ChunkGuard<'a> {
+    chunk: usize,
+    sender: &'a Sender<SimulatorMessage>,
+}
+
+impl Drop for ChunkGuard<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            send_message(self.sender, SimulatorMessage::Failed { chunk: self.chunk });
+        }
+    }
+}
+
+fn spawn_simulator(
+    config: SimulationConfig,
+    fitness: Arc<FitnessFunction>,
+    job_receiver: Arc<Mutex<Receiver<SimulatorMessage>>>,
+    result_sender: Sender<SimulatorMessage>,
+    cancel: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    // a chunk of creatures shares a world, colliding would make a genome's
+    // fitness depend on who it was simulated with
+    let mut world_config = config.world_config.clone();
+    world_config.collision.creature_collision = false;
+    let mut world = World::from_config(&world_config, config.physics);
+
+    thread::spawn(move || loop {
+        let job = match job_receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => break,
+        };
+
+        let (chunk, all_dna) = match job {
+            Ok(SimulatorMessage::Run { chunk, dna }) => (chunk, dna),
+            Ok(SimulatorMessage::Shutdown) | Err(_) => break,
+            Ok(_) => continue,
+        };
+        let _guard = ChunkGuard { chunk, sender: &result_sender };
+
+        // queued chunks of a cancelled generation are answered straight away
+        // so the pool isn't left waiting on them
+        if cancel.load(Ordering::SeqCst) {
+            send_message(&result_sender, SimulatorMessage::Results { chunk, steps: 0, results: Vec::new() });
+            continue;
+        }
+
+        world.reset();
+        for dna in all_dna.iter() {
+            let creature = Creature::new(config.creature_config, dna.clone());
+            if let Some(creature) = creature {
+                world.add_creature(creature);
             }
-        });
+        }
+
+        let dt = config.timestep / config.sub_steps as f64;
+        let total_steps = total_steps(&config);
+        let report_interval = (total_steps / PROGRESS_REPORTS).max(1);
+        let mut steps = 0;
+        while steps < total_steps {
+            if cancel.load(Ordering::Relaxed) {
+                break;
+            }
+
+            world.update(dt);
+            steps += 1;
+
+            if steps % report_interval == 0 && steps < total_steps {
+                send_message(&result_sender, SimulatorMessage::Progress { chunk, steps });
+            }
+        }
+
+        // a creature that blew up or ended in a state the fitness function
+        // can't make sense of must not be able to win with a garbage score
+        let results = if steps == total_steps {
+            let fitnesses = fitness(&world.creatures);
+            all_dna.iter().zip(world.creatures.iter()).zip(fitnesses).map(|((dna, creature), fitness)| {
+                let stable = !creature.unstable && fitness.is_finite();
+                (dna.clone(), Some(fitness).filter(|_| stable))
+            }).collect()
+        } else {
+            Vec::new()
+        };
+        send_message(&result_sender, SimulatorMessage::Results { chunk, steps, results });
+    })
+}
+
+// a fixed set of simulator threads pulling chunks of a generation from a
+// shared queue. dropping the pool cancels any work in progress and joins the
+// threads
+pub struct SimulatorPool {
+    job_sender: Sender<SimulatorMessage>,
+    result_receiver: Receiver<SimulatorMessage>,
+    cancel: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>,
+    steps_per_chunk: usize,
+    unstable_fitness: Option<f64>,
+}
+
+impl SimulatorPool {
+    pub fn from_config(config: SimulationConfig, fitness_func: Arc<FitnessFunction>, cancel: Arc<AtomicBool>) -> SimulatorPool {
+        let (job_sender, job_receiver) = mpsc::channel::<SimulatorMessage>();
+        let (result_sender, result_receiver) = mpsc::channel::<SimulatorMessage>();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..config.threads).map(|_| {
+            spawn_simulator(config.clone(), fitness_func.clone(), job_receiver.clone(), result_sender.clone(), cancel.clone())
+        }).collect();
+
+        SimulatorPool {
+            job_sender,
+            result_receiver,
+            cancel,
+            workers,
+            steps_per_chunk: total_steps(&config),
+            unstable_fitness: config.unstable_fitness,
+        }
+    }
+
+    // results come back in the same order as `dna`, however the chunks were
+    // scheduled. `on_progress` is called with the steps completed so far and
+    // the steps needed for the whole generation, counted per chunk
+    pub fn simulate(&self, dna: &[CreatureDna], mut on_progress: impl FnMut(usize, usize)) -> SimulationOutcome {
+        let chunks: Vec<&[CreatureDna]> = dna.chunks(CHUNK_SIZE).collect();
+        for (chunk, slice) in chunks.iter().enumerate() {
+            let result = self.job_sender.send(SimulatorMessage::Run { chunk, dna: slice.to_vec() });
+            if let Err(msg) = result {
+                return SimulationOutcome::Failed(format!("error while queueing chunk {}: {:?}", chunk, msg));
+            }
+        }
 
-        Simulator {
-            message_sender: sim_tx,
-            message_receiver: sim_rx,
+        let total_steps = self.steps_per_chunk * chunks.len();
+        let mut chunk_steps: Vec<usize> = vec![0; chunks.len()];
+        let mut chunk_results: Vec<Option<Vec<ChunkResult>>> = vec![None; chunks.len()];
+        let mut remaining = chunks.len();
+        let mut failed: Vec<usize> = Vec::new();
+        while remaining > 0 {
+            match self.result_receiver.recv() {
+                Ok(SimulatorMessage::Progress { chunk, steps }) => {
+                    chunk_steps[chunk] = steps;
+                    on_progress(chunk_steps.iter().sum(), total_steps);
+                },
+                Ok(SimulatorMessage::Results { chunk, steps, results }) => {
+                    chunk_steps[chunk] = steps;
+                    chunk_results[chunk] = Some(results);
+                    remaining -= 1;
+                    on_progress(chunk_steps.iter().sum(), total_steps);
+                },
+                // the other chunks are still waited for, so none of their
+                // results are left behind for the next generation
+                Ok(SimulatorMessage::Failed { chunk }) => {
+                    failed.push(chunk);
+                    remaining -= 1;
+                },
+                Ok(_) => {},
+                Err(msg) => return SimulationOutcome::Failed(format!("error while reading channel: {:?}", msg)),
+            }
+        }
+
+        if !failed.is_empty() {
+            return SimulationOutcome::Failed(format!("simulator thread died while simulating chunks {:?}", failed));
+        }
+        if self.cancel.load(Ordering::SeqCst) {
+            return SimulationOutcome::Cancelled;
         }
+
+        let results: Vec<ChunkResult> = chunk_results.into_iter().flatten().flatten().collect();
+        if results.len() != dna.len() {
+            return SimulationOutcome::Failed(format!("expected {} results but only {} came back", dna.len(), results.len()));
+        }
+
+        let penalty = self.unstable_fitness.unwrap_or_else(|| {
+            let worst = results.iter().filter_map(|(_, fitness)| *fitness).min_by(f64::total_cmp);
+            worst.unwrap_or(0.0)
+        });
+        let unstable = results.iter().filter(|(_, fitness)| fitness.is_none()).count();
+        let results = results.into_iter().map(|(dna, fitness)| (dna, fitness.unwrap_or(penalty))).collect();
+        SimulationOutcome::Finished { results, unstable }
     }
+}
+
+impl Drop for SimulatorPool {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::SeqCst);
+        for _ in self.workers.iter() {
+            send_message(&self.job_sender, SimulatorMessage::Shutdown);
+        }
 
-    pub fn start(&self, dna: &[CreatureDna]) {
-        let message = SimulatorMessage::Run(dna.to_vec());
-        let result = self.message_sender.send(message);
-        if let Err(msg) = result {
-            eprintln!("{}: error while starting - {:?}", LOG_OWNER, msg);
+        for worker in self.workers.drain(..) {
+            if worker.join().is_err() {
+                eprintln!("{}: simulator thread panicked", LOG_OWNER);
+            }
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, atomic::AtomicBool};
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::{config::SimulationConfig, dna::{self, CreatureDna}, fitness::FitnessFunction};
+
+    use super::{SimulatorPool, SimulationOutcome, CHUNK_SIZE};
+
+    // one step per genome, the tests are about scheduling
+    fn config(threads: i32) -> SimulationConfig {
+        let mut config = SimulationConfig::default();
+        config.threads = threads;
+        config.sim_time = config.timestep / config.sub_steps as f64;
+        config
+    }
+
+    fn population(config: &SimulationConfig, count: usize) -> Vec<CreatureDna> {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let cells = config.creature_config.size * config.creature_config.size;
+        (0..count).map(|_| dna::generate_dna(cells, &config.mutation_config, &mut rng)).collect()
+    }
+
+    fn pool(config: &SimulationConfig, fitness: FitnessFunction) -> SimulatorPool {
+        SimulatorPool::from_config(config.clone(), Arc::new(fitness), Arc::new(AtomicBool::new(false)))
+    }
+
+    #[test]
+    fn every_genome_comes_back_in_order() {
+        let config = config(6);
+        let pool = pool(&config, Box::new(|creatures| vec![1.0; creatures.len()]));
+
+        // neither a multiple of the thread count nor of the chunk size
+        let dna = population(&config, 1000);
+        assert_ne!(dna.len() % CHUNK_SIZE, 0);
+        match pool.simulate(&dna, |_, _| {}) {
+            SimulationOutcome::Finished { results, .. } => {
+                assert_eq!(results.len(), dna.len());
+                assert!(results.iter().zip(dna.iter()).all(|((result, _), dna)| result == dna));
+            },
+            _ => panic!("simulation didn't finish"),
+        }
+    }
+
+    #[test]
+    fn a_dead_worker_fails_the_generation() {
+        let config = config(2);
+        // only the last, short chunk panics, the other worker stays alive
+        let pool = pool(&config, Box::new(|creatures| {
+            assert_eq!(creatures.len(), CHUNK_SIZE, "simulated panic");
+            vec![1.0; creatures.len()]
+        }));
+
+        let dna = population(&config, CHUNK_SIZE * 3 + 1);
+        assert!(matches!(pool.simulate(&dna, |_, _| {}), SimulationOutcome::Failed(_)));
+    }
+}
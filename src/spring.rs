@@ -1,4 +1,4 @@
-use crate::particle::Particle;
+use crate::{particle::Particle, vec2::Vec2};
 
 pub struct Spring {
     pub a_id: usize,
@@ -6,20 +6,34 @@ pub struct Spring {
     pub length: f64,
     pub start_length: f64,
     pub k: f64,
+    pub damping: f64,
+    pub max_force: Option<f64>,
 }
 
 impl Spring {
-    pub fn apply(&self, particles: &mut Vec<Particle>) {
-        let dir = particles[self.a_id].position - particles[self.b_id].position;
+    // the force on `a`, `b` gets the opposite
+    pub fn force(&self, position_a: Vec2, position_b: Vec2, velocity_a: Vec2, velocity_b: Vec2) -> Vec2 {
+        let dir = position_a - position_b;
         let dist = dir.len();
-        //print!("dist {} :: ", dist);
+        if dist == 0.0 {
+            return Vec2 { x: 0.0, y: 0.0 };
+        }
+        let unit_dir = dir / dist;
 
-        // F = -kx, x is extension ie difference between length & target length
-        let force_mag = self.k * (dist - self.length);
+        // F = -kx - cv, x is extension ie difference between length & target
+        // length, v is how fast the ends move apart along the spring
+        let separation_speed = (velocity_a - velocity_b).dot(unit_dir);
+        let mut force_mag = self.k * (dist - self.length) + self.damping * separation_speed;
+        if let Some(max_force) = self.max_force {
+            force_mag = force_mag.clamp(-max_force, max_force);
+        }
 
-        let unit_dir = dir / dist;
-        particles[self.a_id].add_force(unit_dir * -force_mag);
-        particles[self.b_id].add_force(unit_dir * force_mag);
+        unit_dir * -force_mag
     }
-}
 
+    // extension relative to the current rest length
+    pub fn strain(&self, particles: &[Particle]) -> f64 {
+        let dist = (particles[self.a_id].position - particles[self.b_id].position).len();
+        (dist - self.length) / self.length
+    }
+}
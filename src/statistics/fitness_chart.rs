@@ -25,7 +25,7 @@ fn remap_range(value: f64, lowest: f64, highest: f64) -> f64 {
 }
 
 impl StatisticsPanel for FitnessChart {
-    fn gather_statistics(&mut self, results: &Vec<CreatureResult>) {
+    fn gather_statistics(&mut self, results: &[CreatureResult]) {
         let mut gen_stats: Vec<f64> = Vec::new();
         for percentile in &self.percentiles {
             let id = results.len() as f64 * percentile / 100.0 - 1.0;
@@ -41,14 +41,14 @@ impl StatisticsPanel for FitnessChart {
     fn render(&self, viewport: Viewport, gl: &mut opengl_graphics::GlGraphics, position: Vec2, size: Vec2) {
         let line_color: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
 
+        if self.statistics.is_empty() {
+            return;
+        }
+
         let stat_width = size.x / (self.statistics.len() - 1) as f64;
         let stat_height = size.y;
         let y_off = size.y + position.y;
 
-        if self.statistics.len() < 1 {
-            return;
-        }
-
         let mut highest_fitness = 0.0;
         let mut lowest_fitness = 200.0;
         for percentiles in &self.statistics {
@@ -80,10 +80,10 @@ impl StatisticsPanel for FitnessChart {
         for i in 0..self.statistics.len() - 1 {
             for p in 0..self.statistics[i].len() {
                 let x_a = i as f64 * stat_width;
-                let y_a = remap_range(self.statistics[i][p] as f64, lowest_fitness, highest_fitness);
+                let y_a = remap_range(self.statistics[i][p], lowest_fitness, highest_fitness);
 
                 let x_b = (i + 1) as f64 * stat_width;
-                let y_b = remap_range(self.statistics[i + 1][p] as f64, lowest_fitness, highest_fitness);
+                let y_b = remap_range(self.statistics[i + 1][p], lowest_fitness, highest_fitness);
 
                 let points = [
                     x_a,
@@ -14,6 +14,10 @@ impl Vec2 {
     pub fn len(&self) -> f64 {
         self.sqr_len().sqrt()
     }
+
+    pub fn dot(&self, other: Vec2) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
 }
 
 impl Add<Vec2> for Vec2 {
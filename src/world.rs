@@ -1,30 +1,151 @@
-use crate::{creature::Creature, vec2::Vec2, config::WorldConfig};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{creature::Creature, vec2::Vec2, config::{WorldConfig, TerrainConfig, PhysicsConfig}, ground::Ground, collision::CollisionSolver};
+
+// how far the flat ground continues past the ends of the terrain
+const TERRAIN_EXTENT: f64 = 1e6;
+
+// the ground surface as a polyline sorted by x, solid below. vertical
+// segments (walls, steps) share their x with the neighbouring point
+pub struct Terrain {
+    points: Vec<Vec2>,
+}
+
+impl Terrain {
+    pub fn from_config(config: &TerrainConfig, ground_y: f64) -> Terrain {
+        let point = |x: f64, height: f64| Vec2 { x, y: ground_y - height };
+
+        let mut points = vec![point(-TERRAIN_EXTENT, 0.0)];
+        match config {
+            TerrainConfig::Flat => {},
+            TerrainConfig::Heightmap { start, spacing, heights } => {
+                for (i, height) in heights.iter().enumerate() {
+                    points.push(point(start + i as f64 * spacing, *height));
+                }
+            },
+            TerrainConfig::Ramp { start, length, rise } => {
+                points.push(point(*start, 0.0));
+                points.push(point(start + length, *rise));
+            },
+            TerrainConfig::Steps { start, width, height, count } => {
+                for i in 0..*count {
+                    let x = start + i as f64 * width;
+                    points.push(point(x, i as f64 * height));
+                    points.push(point(x, (i + 1) as f64 * height));
+                }
+            },
+            TerrainConfig::Wall { x, thickness, height } => {
+                points.push(point(*x, 0.0));
+                points.push(point(*x, *height));
+                points.push(point(x + thickness, *height));
+                points.push(point(x + thickness, 0.0));
+            },
+            TerrainConfig::Rough { seed, start, length, spacing, amplitude } => {
+                let mut rng = ChaCha8Rng::seed_from_u64(*seed);
+                let count = (length / spacing) as usize;
+                points.push(point(*start, 0.0));
+                for i in 1..count {
+                    points.push(point(start + i as f64 * spacing, rng.gen::<f64>() * amplitude));
+                }
+                points.push(point(start + length, 0.0));
+            },
+        }
+
+        let last_y = points[points.len() - 1].y;
+        points.push(Vec2 { x: TERRAIN_EXTENT, y: last_y });
+
+        Terrain { points }
+    }
+
+    pub fn points(&self) -> &[Vec2] {
+        &self.points
+    }
+
+    // the topmost surface height at `x`, at a vertical segment this is the
+    // height just to its right
+    pub fn surface_y(&self, x: f64) -> f64 {
+        let right = self.points.partition_point(|p| p.x <= x).clamp(1, self.points.len() - 1);
+        let (a, b) = (self.points[right - 1], self.points[right]);
+        if b.x <= a.x {
+            return b.y;
+        }
+
+        let t = ((x - a.x) / (b.x - a.x)).clamp(0.0, 1.0);
+        a.y + (b.y - a.y) * t
+    }
+
+    // for a position below the surface, the closest point on the surface and
+    // the outward normal there
+    pub fn contact(&self, position: Vec2) -> Option<(Vec2, Vec2)> {
+        let depth = position.y - self.surface_y(position.x);
+        if depth <= 0.0 {
+            return None;
+        }
+
+        // the surface point straight above is `depth` away, so only segments
+        // reaching into that horizontal range can be any closer
+        let first = self.points.partition_point(|p| p.x < position.x - depth).saturating_sub(1);
+        let last = self.points.partition_point(|p| p.x <= position.x + depth).min(self.points.len() - 1);
+
+        let mut closest: Option<(f64, Vec2, Vec2)> = None;
+        for i in first..last {
+            let (a, b) = (self.points[i], self.points[i + 1]);
+            let segment = b - a;
+            let length = segment.len();
+            if length == 0.0 {
+                continue;
+            }
+
+            let t = ((position - a).dot(segment) / (length * length)).clamp(0.0, 1.0);
+            let point = a + segment * t;
+            let distance = (point - position).len();
+            if closest.is_none_or(|(best, _, _)| distance < best) {
+                // pointing away from the solid side, which is below the line
+                let normal = Vec2 { x: segment.y, y: -segment.x } / length;
+                closest = Some((distance, point, normal));
+            }
+        }
+
+        closest.map(|(distance, point, normal)| {
+            // at corners the segment normal is only a guess, the direction
+            // out to the closest point is the real one
+            if distance > 1e-9 {
+                (point, (point - position) / distance)
+            } else {
+                (point, normal)
+            }
+        })
+    }
+}
 
 pub struct World {
     pub creatures: Vec<Creature>,
+    pub ground: Ground,
     pub ground_y: f64,
-    pub ground_friction: f64,
     pub gravity: f64,
+    physics: PhysicsConfig,
+    collisions: CollisionSolver,
 }
 
 impl World {
     pub fn update(&mut self, dt: f64) {
-        for creature in self.creatures.iter_mut() {
+        for creature in self.creatures.iter_mut().filter(|creature| !creature.unstable) {
             for particle in creature.particles.iter_mut() {
                 particle.accelerate(Vec2 { x: 0.0, y: self.gravity });
+            }
 
-                if particle.position.y > self.ground_y {
-                    let velocity = particle.position.x - particle.old_position.x;
-                    particle.accelerate(Vec2 {
-                        x: velocity * -self.ground_friction * self.gravity,
-                        y: 0.0,
-                    });
+            creature.update(dt, self.physics);
+        }
 
-                    particle.position.y = self.ground_y;
-                }
+        // the ground goes last so collisions can't push particles into it
+        self.collisions.resolve(&mut self.creatures);
+        for creature in self.creatures.iter_mut().filter(|creature| !creature.unstable) {
+            for particle in creature.particles.iter_mut() {
+                particle.grounded = self.ground.resolve(&mut particle.position, &mut particle.velocity);
             }
-
-            creature.update(dt);
+            let particles = creature.particles.iter().map(|particle| (particle.mass, particle.position, particle.velocity));
+            creature.energy.record(particles, self.gravity, self.ground_y);
         }
     }
 
@@ -36,13 +157,65 @@ impl World {
         self.creatures.clear();
     }
 
-    pub fn from_config(config: WorldConfig) -> World {
+    pub fn from_config(config: &WorldConfig, physics: PhysicsConfig) -> World {
         World {
             creatures: vec![],
+            ground: Ground::from_config(config),
             ground_y: config.ground_y,
-            ground_friction: config.ground_friction,
             gravity: config.gravity,
+            physics,
+            collisions: CollisionSolver::from_config(config.collision),
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use crate::{config::TerrainConfig, vec2::Vec2};
+
+    use super::Terrain;
+
+    fn assert_near(actual: Vec2, expected: Vec2) {
+        let close = (actual.x - expected.x).abs() < 1e-9 && (actual.y - expected.y).abs() < 1e-9;
+        assert!(close, "got ({}, {}), expected ({}, {})", actual.x, actual.y, expected.x, expected.y);
+    }
+
+    #[test]
+    fn contact_with_a_slope_pushes_out_along_its_normal() {
+        let terrain = Terrain::from_config(&TerrainConfig::Ramp { start: 0.0, length: 100.0, rise: 100.0 }, 500.0);
+        assert!(terrain.contact(Vec2 { x: 50.0, y: 440.0 }).is_none());
+
+        // 10 below the 45 degree slope, up and to the left is out
+        let (point, normal) = terrain.contact(Vec2 { x: 50.0, y: 460.0 }).unwrap();
+        assert_near(point, Vec2 { x: 45.0, y: 455.0 });
+        assert_near(normal, Vec2 { x: -0.5_f64.sqrt(), y: -0.5_f64.sqrt() });
+    }
+
+    #[test]
+    fn contact_inside_a_wall_leaves_through_the_closest_face() {
+        let terrain = Terrain::from_config(&TerrainConfig::Wall { x: 100.0, thickness: 20.0, height: 50.0 }, 500.0);
+
+        let (point, normal) = terrain.contact(Vec2 { x: 102.0, y: 480.0 }).unwrap();
+        assert_near(point, Vec2 { x: 100.0, y: 480.0 });
+        assert_near(normal, Vec2 { x: -1.0, y: 0.0 });
+
+        let (point, normal) = terrain.contact(Vec2 { x: 110.0, y: 455.0 }).unwrap();
+        assert_near(point, Vec2 { x: 110.0, y: 450.0 });
+        assert_near(normal, Vec2 { x: 0.0, y: -1.0 });
+    }
+
+    #[test]
+    fn contact_with_steps_finds_the_riser_and_the_last_height() {
+        let terrain = Terrain::from_config(&TerrainConfig::Steps { start: 0.0, width: 50.0, height: 20.0, count: 3 }, 500.0);
+
+        // just past the second riser, closer to it than to the tread above
+        let (point, normal) = terrain.contact(Vec2 { x: 52.0, y: 465.0 }).unwrap();
+        assert_near(point, Vec2 { x: 50.0, y: 465.0 });
+        assert_near(normal, Vec2 { x: -1.0, y: 0.0 });
+
+        // the top step carries on past the end of the steps
+        let (point, normal) = terrain.contact(Vec2 { x: 1000.0, y: 445.0 }).unwrap();
+        assert_near(point, Vec2 { x: 1000.0, y: 440.0 });
+        assert_near(normal, Vec2 { x: 0.0, y: -1.0 });
+    }
+}